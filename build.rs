@@ -0,0 +1,58 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Hashes everything the app and the --hot-reload library have to agree on, so the
+// app can refuse a library that was built from different code. The types passed
+// between them are plain Rust, so any difference there would be undefined behavior
+// rather than an error. See src/library/hot_reload.rs.
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let mut paths = vec![PathBuf::from("Cargo.toml"), PathBuf::from("Cargo.lock")];
+    add_shared_files(Path::new("src"), &mut paths);
+    paths.sort();
+
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    for path in &paths {
+        hash = add_to_hash(hash, path.to_string_lossy().as_bytes());
+        hash = add_to_hash(hash, &fs::read(path).unwrap_or_default());
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .map(|output| output.stdout)
+        .unwrap_or_default();
+    hash = add_to_hash(hash, &rustc_version);
+    hash = add_to_hash(hash, env::var("PROFILE").unwrap_or_default().as_bytes());
+
+    println!("cargo:rustc-env=HOT_RELOAD_BUILD_HASH={hash}");
+}
+
+// All of src/ except the artworks themselves. src/artwork/mod.rs has the Artwork
+// trait and its params, so it counts.
+fn add_shared_files(folder: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(folder).expect("Couldn't read src/.").flatten() {
+        let path = entry.path();
+
+        if path == Path::new("src").join("artwork") {
+            paths.push(path.join("mod.rs"));
+        } else if path.is_dir() {
+            add_shared_files(&path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+// FNV-1a, since std's hasher isn't guaranteed to stay the same between builds.
+fn add_to_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -2,7 +2,9 @@ use crate::prelude::*;
 
 pub fn render(params: &DrawParams) {
     let mut predictor = ColorPredictor::new(
-        params.app,
+        params
+            .app
+            .expect("The color predictor needs a window to find the assets folder."),
         params.rand.clone(),
         "ny-yayoi-kusama-blurred.jpg",
     );
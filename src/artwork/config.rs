@@ -0,0 +1,357 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Describes an artwork in a JSON or TOML file instead of code, so we can make
+// variations without touching Rust. Each property gets a generator, e.g.
+//
+//   type = "regular_polygons"
+//   num_repeats = 100
+//   resolution = { constant = 25 }
+//   stroke_weight = { denormalize = { generator = "uniform_random_f32", min = 0.001, max = 0.005 } }
+//   color = { looped_hsl = ["#fcc566", "#fea297", "#a4defb"] }
+//
+// Properties that are left out keep the artwork's defaults. The config gets saved
+// in snapshot.json, so it can be reproduced without the file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ArtworkConfig {
+    RegularPolygons {
+        num_repeats: usize,
+        background_color: Option<ColorConfig>,
+        resolution: Option<GeneratorConfig>,
+        center: Option<GeneratorConfig>,
+        stroke_weight: Option<GeneratorConfig>,
+        color: Option<GeneratorConfig>,
+        polygon_is_filled: Option<GeneratorConfig>,
+        radius: Option<GeneratorConfig>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorConfig {
+    // The same value every time.
+    Constant(ConstantConfig),
+    // Between 0 and 1. Usually denormalized to the range we want.
+    UniformRandomF32,
+    UniformRandomUsize {
+        min: usize,
+        max: usize,
+    },
+    UniformRandomBool {
+        probability_of_true: f32,
+    },
+    UniformRandomXy,
+    GridXy {
+        x_resolution: usize,
+        y_resolution: usize,
+    },
+    LoopedHsl(Vec<ColorConfig>),
+    // Maps a generator's output from 0 to 1 to min to max.
+    Denormalize {
+        generator: Box<GeneratorConfig>,
+        min: f32,
+        max: f32,
+    },
+    // Only generates a new value once per draw.
+    Crystallize(Box<GeneratorConfig>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ConstantConfig {
+    Toggle(bool),
+    Number(f32),
+    Point([f32; 2]),
+    Color(ColorConfig),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownFormat(String),
+    // A generator that can't make what the property needs, e.g. a color for a radius.
+    WrongKind {
+        property: &'static str,
+        expected: &'static str,
+    },
+    InvalidColor(ColorConfigError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "Couldn't read the artwork config: {error}"),
+            ConfigError::Json(error) => write!(f, "Couldn't parse the artwork config: {error}"),
+            ConfigError::Toml(error) => write!(f, "Couldn't parse the artwork config: {error}"),
+            ConfigError::UnknownFormat(path) => {
+                write!(f, "{path} should end in .json or .toml.")
+            }
+            ConfigError::WrongKind { property, expected } => {
+                write!(f, "{property} needs a generator that makes {expected}.")
+            }
+            ConfigError::InvalidColor(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(error: serde_json::Error) -> Self {
+        ConfigError::Json(error)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> Self {
+        ConfigError::Toml(error)
+    }
+}
+
+impl From<ColorConfigError> for ConfigError {
+    fn from(error: ColorConfigError) -> Self {
+        ConfigError::InvalidColor(error)
+    }
+}
+
+impl ArtworkConfig {
+    // --artwork-config <path to a .json or .toml file>
+    pub fn from_command_line() -> Option<ArtworkConfig> {
+        let path = command_line::value_of("--artwork-config")?;
+        let config =
+            ArtworkConfig::load(Path::new(&path)).unwrap_or_else(|error| panic!("{error}"));
+
+        Some(config)
+    }
+
+    // No RON, since it'd be another dependency just for configs, and JSON and TOML
+    // already cover the same ground. Post-processing files take the same two.
+    pub fn load(path: &Path) -> Result<ArtworkConfig, ConfigError> {
+        let text = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(serde_json::from_str(&text)?),
+            Some("toml") => Ok(toml::from_str(&text)?),
+            _ => Err(ConfigError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    pub fn create(&self) -> Result<Box<dyn Artwork>, ConfigError> {
+        match self {
+            ArtworkConfig::RegularPolygons {
+                num_repeats,
+                background_color,
+                resolution,
+                center,
+                stroke_weight,
+                color,
+                polygon_is_filled,
+                radius,
+            } => {
+                let mut artwork = regular_polygons().num_repeats(*num_repeats);
+
+                if let Some(background_color) = background_color {
+                    artwork = artwork.background_color(background_color.to_hsl()?);
+                }
+                if let Some(resolution) = resolution {
+                    artwork = artwork.resolution(resolution.usize_generator("resolution")?);
+                }
+                if let Some(center) = center {
+                    artwork = artwork.center(center.point_generator("center")?);
+                }
+                if let Some(stroke_weight) = stroke_weight {
+                    artwork = artwork.stroke_weight(stroke_weight.f32_generator("stroke_weight")?);
+                }
+                if let Some(color) = color {
+                    artwork = artwork.color(color.hsl_generator("color")?);
+                }
+                if let Some(polygon_is_filled) = polygon_is_filled {
+                    artwork = artwork
+                        .polygon_is_filled(polygon_is_filled.bool_generator("polygon_is_filled")?);
+                }
+                if let Some(radius) = radius {
+                    artwork = artwork.radius(radius.f32_generator("radius")?);
+                }
+
+                Ok(Box::new(artwork))
+            }
+        }
+    }
+}
+
+impl GeneratorConfig {
+    pub fn f32_generator(
+        &self,
+        property: &'static str,
+    ) -> Result<ContextGenerator<(), f32>, ConfigError> {
+        let generator = match self {
+            GeneratorConfig::Constant(ConstantConfig::Number(number)) => {
+                (*number).into_context_generator()
+            }
+            GeneratorConfig::UniformRandomF32 => uniform_random_f32().into_context_generator(),
+            GeneratorConfig::Denormalize {
+                generator,
+                min,
+                max,
+            } => generator
+                .f32_generator(property)?
+                .denormalize_generator(*min, *max)
+                .into_context_generator(),
+            GeneratorConfig::Crystallize(generator) => generator
+                .f32_generator(property)?
+                .crystallize()
+                .into_context_generator(),
+            _ => return Err(wrong_kind(property, "numbers")),
+        };
+
+        Ok(generator)
+    }
+
+    pub fn usize_generator(
+        &self,
+        property: &'static str,
+    ) -> Result<ContextGenerator<(), usize>, ConfigError> {
+        let generator = match self {
+            GeneratorConfig::Constant(ConstantConfig::Number(number))
+                if *number >= 0.0 && number.fract() == 0.0 =>
+            {
+                (*number as usize).into_context_generator()
+            }
+            GeneratorConfig::UniformRandomUsize { min, max } => {
+                uniform_random_usize(*min, *max).into_context_generator()
+            }
+            GeneratorConfig::Crystallize(generator) => generator
+                .usize_generator(property)?
+                .crystallize()
+                .into_context_generator(),
+            _ => return Err(wrong_kind(property, "whole numbers")),
+        };
+
+        Ok(generator)
+    }
+
+    pub fn bool_generator(
+        &self,
+        property: &'static str,
+    ) -> Result<ContextGenerator<(), bool>, ConfigError> {
+        let generator = match self {
+            GeneratorConfig::Constant(ConstantConfig::Toggle(is_on)) => {
+                (*is_on).into_context_generator()
+            }
+            GeneratorConfig::UniformRandomBool {
+                probability_of_true,
+            } => context_uniform_random_bool(*probability_of_true).into_context_generator(),
+            GeneratorConfig::Crystallize(generator) => generator
+                .bool_generator(property)?
+                .crystallize()
+                .into_context_generator(),
+            _ => return Err(wrong_kind(property, "true or false")),
+        };
+
+        Ok(generator)
+    }
+
+    pub fn point_generator(
+        &self,
+        property: &'static str,
+    ) -> Result<ContextGenerator<(), Point2>, ConfigError> {
+        let generator = match self {
+            GeneratorConfig::Constant(ConstantConfig::Point([x, y])) => {
+                pt2(*x, *y).into_context_generator()
+            }
+            GeneratorConfig::UniformRandomXy => uniform_random_xy().into_context_generator(),
+            GeneratorConfig::GridXy {
+                x_resolution,
+                y_resolution,
+            } => grid_xy()
+                .dimensions(*x_resolution, *y_resolution)
+                .map_output(|grid_point: GridPoint2| grid_point.xy)
+                .into_context_generator(),
+            GeneratorConfig::Crystallize(generator) => generator
+                .point_generator(property)?
+                .crystallize()
+                .into_context_generator(),
+            _ => return Err(wrong_kind(property, "points")),
+        };
+
+        Ok(generator)
+    }
+
+    pub fn hsl_generator(
+        &self,
+        property: &'static str,
+    ) -> Result<ContextGenerator<(), Hsl>, ConfigError> {
+        let generator = match self {
+            GeneratorConfig::Constant(ConstantConfig::Color(color)) => {
+                color.to_hsl()?.into_context_generator()
+            }
+            GeneratorConfig::LoopedHsl(colors) => {
+                let mut looped = looped_hsl();
+                for color in colors {
+                    looped = looped.color(color.to_hsl()?);
+                }
+                looped.into_context_generator()
+            }
+            GeneratorConfig::Crystallize(generator) => generator
+                .hsl_generator(property)?
+                .crystallize()
+                .into_context_generator(),
+            _ => return Err(wrong_kind(property, "colors")),
+        };
+
+        Ok(generator)
+    }
+}
+
+fn wrong_kind(property: &'static str, expected: &'static str) -> ConfigError {
+    ConfigError::WrongKind { property, expected }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn parses_generators_and_rejects_the_wrong_kind() {
+        let config: ArtworkConfig = toml::from_str(
+            r##"
+                type = "regular_polygons"
+                num_repeats = 10
+                resolution = { uniform_random_usize = { min = 3, max = 8 } }
+                radius = { crystallize = { denormalize = { generator = "uniform_random_f32", min = 0.01, max = 0.02 } } }
+                color = { looped_hsl = ["#fca297", { hue = 0.5, saturation = 0.5, lightness = 0.5 }] }
+                polygon_is_filled = { constant = true }
+            "##,
+        )
+        .unwrap();
+
+        assert!(config.create().is_ok());
+
+        let radius = GeneratorConfig::Denormalize {
+            generator: Box::new(GeneratorConfig::UniformRandomF32),
+            min: 0.01,
+            max: 0.02,
+        };
+        let rand = Rand::from_seed(1);
+        let mut generator = radius.f32_generator("radius").unwrap();
+        let value = generator.generate(&rand, ());
+        assert!((0.01..=0.02).contains(&value));
+
+        assert!(matches!(
+            radius.hsl_generator("color"),
+            Err(ConfigError::WrongKind { .. })
+        ));
+    }
+}
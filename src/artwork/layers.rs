@@ -0,0 +1,358 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Draws several artworks in one render, each in its own part of the container and
+// with its own number of repeats, blend mode and mask.
+//
+//   layers()
+//       .layer(layer(regular_polygons()).num_repeats(50).blend_mode(BlendMode::Multiply))
+//       .layer(layer(other_artwork).container(Rect::from_corners(pt2(0.5, 0.0), pt2(1.0, 1.0))))
+//       .interleaved()
+//
+// The render is done when every layer is done.
+pub fn layers() -> Layers {
+    Layers {
+        layers: vec![],
+        order: LayerOrder::Sequence,
+        background_color: None,
+        next_layer_index: 0,
+    }
+}
+
+pub fn layer(artwork: impl Artwork + 'static) -> Layer {
+    let num_repeats = artwork.num_repeats();
+
+    Layer {
+        artwork: Box::new(artwork),
+        num_repeats,
+        num_drawn: 0,
+        container: Rect::from_corners(pt2(0.0, 0.0), pt2(1.0, 1.0)),
+        mask: None,
+        blend_mode: BlendMode::Normal,
+        has_warned_about_mask: false,
+    }
+}
+
+pub struct Layers {
+    layers: Vec<Layer>,
+    order: LayerOrder,
+    // Falls back to the first layer's background color.
+    background_color: Option<Hsl>,
+    // Where to carry on from when interleaving.
+    next_layer_index: usize,
+}
+
+pub enum LayerOrder {
+    // Finishes each layer before starting the next.
+    Sequence,
+    // Takes turns, one draw from each layer that isn't done yet.
+    Interleaved,
+}
+
+pub struct Layer {
+    artwork: Box<dyn Artwork>,
+    num_repeats: usize,
+    num_drawn: usize,
+    // In the container's normalized coordinates.
+    container: Rect,
+    // In the layer's normalized coordinates.
+    mask: Option<Path2>,
+    blend_mode: BlendMode,
+    has_warned_about_mask: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayersCheckpointState {
+    num_drawn: Vec<usize>,
+    next_layer_index: usize,
+    artwork_states: Vec<Option<serde_json::Value>>,
+}
+
+impl Layers {
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn sequence(mut self) -> Self {
+        self.order = LayerOrder::Sequence;
+        self
+    }
+
+    pub fn interleaved(mut self) -> Self {
+        self.order = LayerOrder::Interleaved;
+        self
+    }
+
+    pub fn background_color(mut self, background_color: Hsl) -> Self {
+        self.background_color = Some(background_color);
+        self
+    }
+
+    fn choose_next_layer(&mut self) -> Option<usize> {
+        let num_layers = self.layers.len();
+
+        match self.order {
+            LayerOrder::Sequence => self.layers.iter().position(|layer| !layer.is_done()),
+            LayerOrder::Interleaved => {
+                let layer_index = (0..num_layers)
+                    .map(|offset| (self.next_layer_index + offset) % num_layers)
+                    .find(|layer_index| !self.layers[*layer_index].is_done())?;
+
+                self.next_layer_index = layer_index + 1;
+                Some(layer_index)
+            }
+        }
+    }
+}
+
+impl Layer {
+    pub fn num_repeats(mut self, num_repeats: usize) -> Self {
+        self.num_repeats = num_repeats;
+        self
+    }
+
+    pub fn container(mut self, container: Rect) -> Self {
+        self.container = container;
+        self
+    }
+
+    // The mask only applies to what the artwork draws on params.canvas. Anything it
+    // draws on params.draw goes straight to nannou, which can't clip to a path, so
+    // it shows up unmasked in the window.
+    pub fn mask(mut self, mask: Path2) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    // Applies to params.draw too, as far as nannou's blending can copy it.
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    fn is_done(&self) -> bool {
+        self.num_drawn >= self.num_repeats
+    }
+
+    fn draw(&mut self, params: &DrawParams) {
+        let container = &self.container;
+
+        let canvas = params
+            .canvas
+            .layer(*container, self.mask.as_ref(), self.blend_mode);
+
+        if self.mask.is_some() && params.app.is_some() && !self.has_warned_about_mask {
+            println!("A layer has a mask, but the mask doesn't apply to anything drawn on params.draw, so the window might not match the saved image.");
+            self.has_warned_about_mask = true;
+        }
+
+        // For anything drawn with nannou directly instead of through the canvas.
+        let draw = params
+            .draw
+            .color_blend(self.blend_mode.blend_component())
+            .translate(container.bottom_left().extend(0.0))
+            .scale_axes(vec3(container.w(), container.h(), 1.0));
+
+        let denormalized_container = params.container.denormalize_rect(container);
+
+        let max_draw_index = self.num_repeats.saturating_sub(1).max(1);
+
+        let layer_params = DrawParams {
+            app: params.app,
+            rand: params.rand,
+            draw: &draw,
+            canvas: &canvas,
+            model: params.model,
+            container: &denormalized_container,
+            progress_through_whole_drawing: self.num_drawn as f32 / max_draw_index as f32,
+        };
+
+        self.artwork.draw(&layer_params);
+        self.num_drawn += 1;
+    }
+}
+
+impl Artwork for Layers {
+    fn draw(&mut self, params: &DrawParams) {
+        if let Some(layer_index) = self.choose_next_layer() {
+            self.layers[layer_index].draw(params);
+        }
+    }
+
+    fn num_repeats(&self) -> usize {
+        self.layers.iter().map(|layer| layer.num_repeats).sum()
+    }
+
+    fn background_color(&self) -> Hsl {
+        self.background_color
+            .or_else(|| {
+                self.layers
+                    .first()
+                    .map(|layer| layer.artwork.background_color())
+            })
+            .unwrap_or_else(soft_white)
+    }
+
+    fn done_drawing(&self) -> DoneDrawing {
+        let layers_are_done = self
+            .layers
+            .iter()
+            .map(|layer| DoneDrawing::from(layer.is_done()))
+            .collect();
+
+        DoneDrawing::all_done(layers_are_done)
+    }
+
+    fn checkpoint_state(&self) -> Option<serde_json::Value> {
+        let state = LayersCheckpointState {
+            num_drawn: self.layers.iter().map(|layer| layer.num_drawn).collect(),
+            next_layer_index: self.next_layer_index,
+            artwork_states: self
+                .layers
+                .iter()
+                .map(|layer| layer.artwork.checkpoint_state())
+                .collect(),
+        };
+
+        serde_json::to_value(state).ok()
+    }
+
+    fn restore_checkpoint_state(
+        &mut self,
+        state: serde_json::Value,
+    ) -> Result<(), CheckpointStateError> {
+        let state: LayersCheckpointState = serde_json::from_value(state)?;
+
+        // Otherwise the layers would carry on from each other's progress.
+        let num_layers = self.layers.len();
+        for num_saved in [state.num_drawn.len(), state.artwork_states.len()] {
+            if num_saved != num_layers {
+                return Err(CheckpointStateError::NumLayersChanged {
+                    num_saved,
+                    num_layers,
+                });
+            }
+        }
+
+        self.next_layer_index = state.next_layer_index;
+
+        for ((layer, num_drawn), artwork_state) in self
+            .layers
+            .iter_mut()
+            .zip(state.num_drawn)
+            .zip(state.artwork_states)
+        {
+            layer.num_drawn = num_drawn;
+
+            if let Some(artwork_state) = artwork_state {
+                layer.artwork.restore_checkpoint_state(artwork_state)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    struct Dot {
+        center: Point2,
+    }
+
+    impl Artwork for Dot {
+        fn draw(&mut self, params: &DrawParams) {
+            params
+                .canvas
+                .ellipse(self.center, vec2(0.1, 0.1), soft_black());
+        }
+
+        fn num_repeats(&self) -> usize {
+            2
+        }
+
+        fn background_color(&self) -> Hsl {
+            soft_white()
+        }
+    }
+
+    #[test]
+    fn interleaves_layers_inside_their_containers() {
+        let right_half = Rect::from_corners(pt2(0.5, 0.0), pt2(1.0, 1.0));
+
+        let mut artwork = layers()
+            .layer(layer(Dot {
+                center: pt2(0.5, 0.5),
+            }))
+            .layer(
+                layer(Dot {
+                    center: pt2(0.5, 0.5),
+                })
+                .num_repeats(1)
+                .container(right_half),
+            )
+            .interleaved();
+
+        assert_eq!(artwork.num_repeats(), 3);
+
+        let model = Model::unsaved(1);
+        let rand = model.snapshot.get_rand();
+        let draw = Draw::new();
+        let canvas = Canvas::headless();
+
+        let params = DrawParams {
+            app: None,
+            rand: &rand,
+            draw: &draw,
+            canvas: &canvas,
+            model: &model,
+            container: &model.container,
+            progress_through_whole_drawing: 0.0,
+        };
+
+        for _ in 0..3 {
+            assert!(!artwork.done_drawing().to_bool());
+            artwork.draw(&params);
+        }
+        assert!(artwork.done_drawing().to_bool());
+
+        let centers: Vec<Point2> = canvas
+            .take_recorded_shapes()
+            .into_iter()
+            .map(|shape| match shape {
+                Shape::Ellipse { center, .. } => center,
+                other => panic!("Expected an ellipse, got {other:?}."),
+            })
+            .collect();
+
+        assert_eq!(centers, vec![pt2(0.5, 0.5), pt2(0.75, 0.5), pt2(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn restoring_fails_when_the_number_of_layers_changed() {
+        let dot = || {
+            layer(Dot {
+                center: pt2(0.5, 0.5),
+            })
+        };
+        let state = layers()
+            .layer(dot())
+            .layer(dot())
+            .checkpoint_state()
+            .unwrap();
+
+        let mut same_layers = layers().layer(dot()).layer(dot());
+        assert!(same_layers.restore_checkpoint_state(state.clone()).is_ok());
+
+        let mut fewer_layers = layers().layer(dot());
+        assert!(matches!(
+            fewer_layers.restore_checkpoint_state(state),
+            Err(CheckpointStateError::NumLayersChanged {
+                num_saved: 2,
+                num_layers: 1
+            })
+        ));
+    }
+}
@@ -5,6 +5,12 @@ mod box_shadow;
 pub mod color_swatch;
 pub use color_swatch::*;
 
+pub mod config;
+pub use config::*;
+
+pub mod layers;
+pub use layers::*;
+
 mod color_palette;
 mod color_predictor;
 
@@ -21,18 +27,47 @@ pub trait Artwork {
     fn draw(&mut self, params: &DrawParams);
     fn num_repeats(&self) -> usize;
     fn background_color(&self) -> Hsl;
+
+    // Artworks that build up state between draws can save it in checkpoints, so
+    // resumed renders carry on exactly where they left off. Most don't need to.
+    fn checkpoint_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    fn restore_checkpoint_state(
+        &mut self,
+        _state: serde_json::Value,
+    ) -> Result<(), CheckpointStateError> {
+        Ok(())
+    }
+
+    // Filters for the saved images, like grain or a vignette. --post-process
+    // replaces them.
+    fn post_processing(&self) -> PostProcessing {
+        post_processing()
+    }
+
+    // Lets an artwork finish before num_repeats, e.g. when it's made of layers that
+    // keep track of their own repeats.
+    fn done_drawing(&self) -> DoneDrawing {
+        DoneDrawing::No
+    }
 }
 
 pub struct DrawParams<'a> {
-    pub app: &'a App,
+    // None when rendering headlessly.
+    pub app: Option<&'a App>,
     pub rand: &'a Rand,
     pub draw: &'a Draw,
+    pub canvas: &'a Canvas,
     pub model: &'a Model,
     pub container: &'a Rect,
     pub progress_through_whole_drawing: NormalizedF32,
 }
 
 impl<'a> DrawParams<'a> {
+    // Each draw gets the same numbers every time the artwork renders, however the
+    // draws are split into frames.
     pub fn rand_that_stays_the_same_every_frame(&self) -> &'a Rand {
         self.rand
     }
@@ -40,6 +75,12 @@ impl<'a> DrawParams<'a> {
     pub fn rand_that_changes_every_frame(&self) -> &'a Rand {
         &self.model.rand
     }
+
+    // Random values that only depend on the seed, the name and an index, so they
+    // don't change when other parts of the artwork use more or less randomness.
+    pub fn rand_stream(&self, name: &str) -> RandStream {
+        self.rand.stream(name)
+    }
 }
 
 pub enum DoneDrawing {
@@ -103,14 +103,9 @@ impl Artwork for RegularPolygons {
         let polygon_is_filled = self.polygon_is_filled_generator.generate(rand, ());
 
         if polygon_is_filled {
-            params.draw.polygon().points(path).color(color);
+            params.canvas.polygon(path, color);
         } else {
-            params
-                .draw
-                .polyline()
-                .stroke_weight(stroke_weight)
-                .points(path)
-                .color(color);
+            params.canvas.polyline(path, stroke_weight, color);
         }
     }
 
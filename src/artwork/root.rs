@@ -1,22 +1,26 @@
 use crate::prelude::*;
 
-pub fn create(_params: CreateArtworkParams) -> RegularPolygons {
+pub fn create(params: CreateArtworkParams) -> RegularPolygons {
     // let radius = sine().frequency(1.0).denormalize(0.003, 0.019);
     // let radius = sine().frequency(1.0).denormalize(0.003, 0.019);
     // .crystallize();
 
+    let parameters = params.parameters;
+    let max_stroke_weight = parameters.slider("max_stroke_weight", 0.005, 0.001..=0.02);
+
     regular_polygons()
-        .num_repeats(100)
+        .num_repeats(parameters.whole_number_slider("num_repeats", 100, 1..=1000))
         // .resolution(uniform_random_usize(5, 25))
         // .resolution(10)
-        .resolution(25)
-        .stroke_weight(uniform_random_f32().denormalize_generator(0.001, 0.005))
+        .resolution(parameters.whole_number_slider("resolution", 25, 3..=50))
+        .stroke_weight(uniform_random_f32().denormalize_generator(0.001, max_stroke_weight))
         .color(
             looped_hsl()
-                .color_picker(252, 197, 102, 255)
-                .color_picker(254, 162, 151, 255)
-                .color_picker(164, 222, 251, 255),
+                .color(parameters.color("first_color", Colors::color_picker(252, 197, 102, 255)))
+                .color(parameters.color("second_color", Colors::color_picker(254, 162, 151, 255)))
+                .color(parameters.color("third_color", Colors::color_picker(164, 222, 251, 255))),
         )
+        .background_color(parameters.color("background_color", soft_white()))
         .radius(radius())
         .center(
             // grid_xy()
@@ -24,7 +28,7 @@ pub fn create(_params: CreateArtworkParams) -> RegularPolygons {
             //     .map_output(|grid_point| grid_point.xy),
             random_center(),
         )
-        .polygon_is_filled(false)
+        .polygon_is_filled(parameters.toggle("polygon_is_filled", false))
 }
 
 fn random_center() -> impl IntoContextGenerator<(), Point2> {
@@ -100,7 +104,9 @@ fn radius() -> impl IntoContextGenerator<(), f32> {
 }
 
 pub struct CreateArtworkParams<'a> {
-    pub app: &'a App,
+    // None when rendering headlessly.
+    pub app: Option<&'a App>,
     pub rand: &'a Rand,
     pub container: &'a Rect,
+    pub parameters: &'a Parameters,
 }
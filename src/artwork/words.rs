@@ -1,14 +1,14 @@
 use crate::prelude::*;
 
 pub fn new(params: &DrawParams) {
-    let draw = params.app.draw();
+    let app = params.app.expect("Drawing words needs a window.");
+    let draw = app.draw();
     let container = params.container;
     let text_container = container.scale(0.8);
     let text_container_wh = text_container.wh();
     let font_size = text_container_wh.x.times(0.03) as u32;
 
-    let font_path = params
-        .app
+    let font_path = app
         .assets_path()
         .unwrap()
         .join("Inter")
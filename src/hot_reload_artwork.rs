@@ -0,0 +1,35 @@
+#![allow(dead_code)]
+#![allow(non_upper_case_globals)]
+#![allow(clippy::too_many_arguments)]
+
+// The artwork, built as a dynamic library for --hot-reload. See
+// library/hot_reload.rs. It has its own copy of the rest of src/, which has to
+// match the app's.
+
+mod artwork;
+mod colors;
+mod crates;
+mod library;
+mod model;
+mod prelude;
+mod window_dimensions;
+
+use prelude::*;
+
+// Called first, before anything that needs the rest of src/ to match the app's.
+// See build.rs.
+#[no_mangle]
+pub extern "C" fn build_hash() -> u64 {
+    library::build_hash()
+}
+
+// Called by the app right after loading, since we don't share its statics.
+#[no_mangle]
+pub fn share_globals(globals: SharedGlobals) {
+    globals.use_in_this_library();
+}
+
+#[no_mangle]
+pub fn create_artwork(params: CreateArtworkParams) -> Box<dyn Artwork> {
+    Box::new(artwork::create(params))
+}
@@ -0,0 +1,168 @@
+use crate::prelude::*;
+use geo::Contains;
+
+// Cuts a shape down to the parts inside every mask. Ellipses become polygons, since
+// the part of an ellipse inside a mask usually isn't an ellipse anymore.
+pub fn clip_to_masks(shape: Shape, masks: &[Path2]) -> Vec<Shape> {
+    masks.iter().fold(vec![shape], |shapes, mask| {
+        shapes
+            .into_iter()
+            .flat_map(|shape| clip_to_mask(shape, mask))
+            .collect()
+    })
+}
+
+fn clip_to_mask(shape: Shape, mask: &Path2) -> Vec<Shape> {
+    match shape {
+        Shape::Polyline {
+            points,
+            stroke_weight,
+            color,
+        } => clip_polyline(&points, mask)
+            .into_iter()
+            .map(|points| Shape::Polyline {
+                points,
+                stroke_weight,
+                color,
+            })
+            .collect(),
+
+        Shape::Polygon { points, color } => points
+            .intersections(mask)
+            .into_iter()
+            .map(|points| Shape::Polygon { points, color })
+            .collect(),
+
+        Shape::Ellipse { center, wh, color } => {
+            let points = Path2::ellipse(Rect::from_xy_wh(center, wh), 64);
+            clip_to_mask(Shape::Polygon { points, color }, mask)
+        }
+
+        Shape::Blended { shape, blend_mode } => clip_to_mask(*shape, mask)
+            .into_iter()
+            .map(|shape| Shape::Blended {
+                shape: Box::new(shape),
+                blend_mode,
+            })
+            .collect(),
+
+        // Layers are masked when they're composited.
+        shape @ Shape::Pixels { .. } => vec![shape],
+    }
+}
+
+// The pieces of the polyline inside the mask. We split each segment wherever it
+// crosses the mask's outline, then keep the pieces whose middle is inside.
+pub fn clip_polyline(points: &Path2, mask: &Path2) -> Vec<Path2> {
+    let mask_polygon = mask.as_geo_polygon();
+    let is_inside = |point: Point2| mask_polygon.contains(&geo::Point::new(point.x, point.y));
+
+    let mut pieces: Vec<Path2> = vec![];
+    let mut current_piece: Path2 = vec![];
+
+    for segment in points.windows(2) {
+        let [start, end] = [segment[0], segment[1]];
+
+        let mut crossings: Vec<f32> = mask
+            .iter()
+            .zip(mask.iter().cycle().skip(1))
+            .filter_map(|(mask_start, mask_end)| {
+                segment_crossing(start, end, *mask_start, *mask_end)
+            })
+            .collect();
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut boundaries = vec![0.0];
+        boundaries.extend(crossings);
+        boundaries.push(1.0);
+
+        for window in boundaries.windows(2) {
+            let [from, to] = [window[0], window[1]];
+            if to - from <= f32::EPSILON {
+                continue;
+            }
+
+            let middle = start.lerp(end, (from + to) / 2.0);
+
+            if is_inside(middle) {
+                if current_piece.is_empty() {
+                    current_piece.push(start.lerp(end, from));
+                }
+                current_piece.push(start.lerp(end, to));
+            } else if !current_piece.is_empty() {
+                pieces.push(std::mem::take(&mut current_piece));
+            }
+        }
+    }
+
+    if current_piece.len() >= 2 {
+        pieces.push(current_piece);
+    }
+
+    pieces.retain(|piece| piece.len() >= 2);
+    pieces
+}
+
+// How far along the first segment it crosses the second one, from 0 to 1.
+fn segment_crossing(
+    start: Point2,
+    end: Point2,
+    other_start: Point2,
+    other_end: Point2,
+) -> Option<f32> {
+    let direction = end - start;
+    let other_direction = other_end - other_start;
+
+    let denominator = direction.perp_dot(other_direction);
+    if denominator.abs() < f32::EPSILON {
+        return None; // Parallel.
+    }
+
+    let offset = other_start - start;
+    let progress = offset.perp_dot(other_direction) / denominator;
+    let other_progress = offset.perp_dot(direction) / denominator;
+
+    let crosses = (0.0..=1.0).contains(&progress) && (0.0..=1.0).contains(&other_progress);
+    crosses.then(|| progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn keeps_only_the_parts_of_a_polyline_inside_the_mask() {
+        let mask = vec![
+            pt2(0.25, 0.25),
+            pt2(0.75, 0.25),
+            pt2(0.75, 0.75),
+            pt2(0.25, 0.75),
+        ];
+
+        // Goes in and out of the mask twice.
+        let zigzag = vec![
+            pt2(0.0, 0.5),
+            pt2(0.5, 0.5),
+            pt2(0.5, 1.0),
+            pt2(0.6, 1.0),
+            pt2(0.6, 0.0),
+        ];
+
+        let pieces = clip_polyline(&zigzag, &mask);
+
+        assert_eq!(pieces.len(), 2);
+
+        let expected_first_piece = [pt2(0.25, 0.5), pt2(0.5, 0.5), pt2(0.5, 0.75)];
+        let expected_second_piece = [pt2(0.6, 0.75), pt2(0.6, 0.25)];
+
+        for (piece, expected) in pieces
+            .iter()
+            .zip([&expected_first_piece[..], &expected_second_piece[..]])
+        {
+            assert_eq!(piece.len(), expected.len());
+            for (point, expected_point) in piece.iter().zip(expected) {
+                assert!(point.distance(*expected_point) < 1e-5);
+            }
+        }
+    }
+}
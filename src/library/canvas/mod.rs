@@ -0,0 +1,512 @@
+use crate::prelude::*;
+use nannou::window::Window;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use tiny_skia::Pixmap;
+
+mod clipping;
+pub use clipping::*;
+
+mod offscreen;
+pub use offscreen::*;
+
+mod rasterizer;
+pub use rasterizer::*;
+
+pub mod svg;
+
+// Everything drawn through the canvas is remembered as one of these shapes, in
+// normalized container coordinates. That's what lets us render an artwork without
+// a window: the shapes get rasterized on the CPU instead of by nannou.
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Polyline {
+        points: Path2,
+        stroke_weight: NormalizedF32,
+        color: Hsla,
+    },
+    Polygon {
+        points: Path2,
+        color: Hsla,
+    },
+    Ellipse {
+        center: Point2,
+        wh: Vec2,
+        color: Hsla,
+    },
+    // Drawn on a layer with a blend mode other than normal.
+    Blended {
+        shape: Box<Shape>,
+        blend_mode: BlendMode,
+    },
+    // A composited offscreen layer, the same size as the window.
+    Pixels {
+        pixmap: Rc<Pixmap>,
+        blend_mode: BlendMode,
+    },
+}
+
+// How shapes on a layer combine with what's already been drawn underneath.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Darken,
+    Lighten,
+    Add,
+    Overlay,
+    // Mixes colors like paint, with Hsl::mix_pigment.
+    PigmentMix,
+}
+
+pub struct Canvas {
+    // Only present when we're drawing to a window.
+    draw: Option<Draw>,
+    // Shared with the layers made from this canvas.
+    recorded_shapes: Rc<RefCell<Vec<Shape>>>,
+    is_recording: bool,
+    // Where the layer's normalized points end up on the whole canvas.
+    container: Option<Rect>,
+    // In whole canvas coordinates. Shapes only show up inside all of them.
+    masks: Vec<Path2>,
+    blend_mode: BlendMode,
+    // Shared by every canvas drawing the same render.
+    offscreen_layers: Rc<RefCell<OffscreenLayers>>,
+    // Set on canvases made with offscreen. Their shapes go to that layer instead.
+    offscreen_layer_name: Option<String>,
+    // For uploading composited layers to the window.
+    device_queue_pair: Option<Arc<nannou::wgpu::DeviceQueuePair>>,
+}
+
+impl Canvas {
+    // Forwards everything to nannou. The draw instance should already be scaled so
+    // that it accepts normalized points.
+    pub fn windowed(draw: Draw, window: &Window) -> Canvas {
+        Canvas {
+            draw: Some(draw),
+            recorded_shapes: Rc::new(RefCell::new(vec![])),
+            is_recording: false,
+            container: None,
+            masks: vec![],
+            blend_mode: BlendMode::Normal,
+            offscreen_layers: Rc::new(RefCell::new(OffscreenLayers::default())),
+            offscreen_layer_name: None,
+            device_queue_pair: Some(Arc::clone(window.device_queue_pair())),
+        }
+    }
+
+    // Doesn't need a window. Shapes are only recorded, so they can be rasterized later.
+    pub fn headless() -> Canvas {
+        Canvas {
+            draw: None,
+            recorded_shapes: Rc::new(RefCell::new(vec![])),
+            is_recording: true,
+            container: None,
+            masks: vec![],
+            blend_mode: BlendMode::Normal,
+            offscreen_layers: Rc::new(RefCell::new(OffscreenLayers::default())),
+            offscreen_layer_name: None,
+            device_queue_pair: None,
+        }
+    }
+
+    // Windowed canvases can record too, e.g. when we're exporting an SVG.
+    pub fn recording(mut self, is_recording: bool) -> Canvas {
+        self.is_recording = self.is_recording || is_recording;
+        self
+    }
+
+    // Offscreen layers only last as long as the canvas, unless they're shared
+    // with the canvases for the following frames.
+    pub fn offscreen_layers(mut self, offscreen_layers: &Rc<RefCell<OffscreenLayers>>) -> Canvas {
+        self.offscreen_layers = Rc::clone(offscreen_layers);
+        self
+    }
+
+    // A canvas for drawing part of the artwork. Its normalized points fill the
+    // container, which is in this canvas's normalized coordinates. So is the mask,
+    // relative to the container. Everything drawn on it ends up on this canvas.
+    pub fn layer(&self, container: Rect, mask: Option<&Path2>, blend_mode: BlendMode) -> Canvas {
+        let bottom_left = self.to_canvas_point(container.bottom_left());
+        let top_right = self.to_canvas_point(container.top_right());
+        let layer_container = Rect::from_corners(bottom_left, top_right);
+
+        let mut masks = self.masks.clone();
+        if let Some(mask) = mask {
+            masks.push(
+                mask.iter()
+                    .map(|point| {
+                        let point = pt2(
+                            container.left() + point.x * container.w(),
+                            container.bottom() + point.y * container.h(),
+                        );
+                        self.to_canvas_point(point)
+                    })
+                    .collect(),
+            );
+        }
+
+        Canvas {
+            draw: self
+                .draw
+                .as_ref()
+                .map(|draw| draw.color_blend(blend_mode.blend_component())),
+            recorded_shapes: Rc::clone(&self.recorded_shapes),
+            is_recording: self.is_recording,
+            container: Some(layer_container),
+            masks,
+            blend_mode,
+            offscreen_layers: Rc::clone(&self.offscreen_layers),
+            offscreen_layer_name: self.offscreen_layer_name.clone(),
+            device_queue_pair: self.device_queue_pair.clone(),
+        }
+    }
+
+    // A canvas that draws into the named offscreen layer instead. Nothing drawn on it
+    // shows up until the layer is composited.
+    pub fn offscreen(&self, layer_name: &str) -> Canvas {
+        Canvas {
+            draw: None,
+            recorded_shapes: Rc::clone(&self.recorded_shapes),
+            is_recording: false,
+            container: self.container,
+            masks: self.masks.clone(),
+            blend_mode: self.blend_mode,
+            offscreen_layers: Rc::clone(&self.offscreen_layers),
+            offscreen_layer_name: Some(layer_name.to_string()),
+            device_queue_pair: self.device_queue_pair.clone(),
+        }
+    }
+
+    // Flattens the named offscreen layer onto this canvas. Call draw last, since
+    // that's when it happens. The layer starts over empty afterwards.
+    //
+    //   let wash = params.canvas.offscreen("wash");
+    //   wash.polygon(points, hsla(0.6, 0.5, 0.5, 0.1));
+    //   params.canvas.composite("wash").blend_mode(BlendMode::Multiply).mask(circle).draw();
+    pub fn composite(&self, layer_name: &str) -> CanvasComposite<'_> {
+        CanvasComposite {
+            canvas: self,
+            layer_name: layer_name.to_string(),
+            blend_mode: BlendMode::Normal,
+            opacity: 1.0,
+            masks: vec![],
+        }
+    }
+
+    pub fn polyline(&self, points: Path2, stroke_weight: NormalizedF32, color: impl Into<Hsla>) {
+        let color = color.into();
+
+        self.add(Shape::Polyline {
+            points: self.to_canvas_points(points),
+            stroke_weight: self.to_canvas_width(stroke_weight),
+            color,
+        });
+    }
+
+    pub fn polygon(&self, points: Path2, color: impl Into<Hsla>) {
+        let color = color.into();
+
+        self.add(Shape::Polygon {
+            points: self.to_canvas_points(points),
+            color,
+        });
+    }
+
+    pub fn ellipse(&self, center: Point2, wh: Vec2, color: impl Into<Hsla>) {
+        let color = color.into();
+
+        let wh = match &self.container {
+            Some(container) => wh * container.wh(),
+            None => wh,
+        };
+
+        self.add(Shape::Ellipse {
+            center: self.to_canvas_point(center),
+            wh,
+            color,
+        });
+    }
+
+    // Works like DrawExtension::polylines, but the polylines get recorded too.
+    // Call points last, since that's when the polylines are drawn.
+    pub fn polylines(&self, num_polylines: usize) -> CanvasPolylines<'_> {
+        CanvasPolylines {
+            canvas: self,
+            num_polylines,
+            stroke_weight: 1.0,
+            color: soft_black().into(),
+        }
+    }
+
+    pub fn take_recorded_shapes(&self) -> Vec<Shape> {
+        self.recorded_shapes.replace(vec![])
+    }
+
+    fn add(&self, shape: Shape) {
+        for shape in clip_to_masks(shape, &self.masks) {
+            let shape = match self.blend_mode {
+                BlendMode::Normal => shape,
+                blend_mode => Shape::Blended {
+                    shape: Box::new(shape),
+                    blend_mode,
+                },
+            };
+
+            if let Some(layer_name) = &self.offscreen_layer_name {
+                self.offscreen_layers
+                    .borrow_mut()
+                    .layer(layer_name)
+                    .rasterize(&[shape]);
+                continue;
+            }
+
+            if let Some(draw) = &self.draw {
+                draw_with_nannou(draw, &shape);
+            }
+
+            self.record(shape);
+        }
+    }
+
+    // Masks have already been applied to the pixels. The name is the layer they
+    // came from.
+    fn add_pixels(&self, layer_name: &str, pixmap: Pixmap, blend_mode: BlendMode) {
+        let pixmap = Rc::new(pixmap);
+
+        if let Some(offscreen_layer_name) = &self.offscreen_layer_name {
+            self.offscreen_layers
+                .borrow_mut()
+                .layer(offscreen_layer_name)
+                .rasterize(&[Shape::Pixels { pixmap, blend_mode }]);
+            return;
+        }
+
+        if let (Some(draw), Some(device_queue_pair)) = (&self.draw, &self.device_queue_pair) {
+            let mut offscreen_layers = self.offscreen_layers.borrow_mut();
+            let texture = offscreen_layers.texture(layer_name, device_queue_pair, &pixmap);
+            draw_pixels_with_nannou(draw, texture, blend_mode);
+        }
+
+        let shape = Shape::Pixels { pixmap, blend_mode };
+        self.record(shape);
+    }
+
+    fn record(&self, shape: Shape) {
+        if self.is_recording {
+            self.recorded_shapes.borrow_mut().push(shape);
+        }
+    }
+
+    fn to_canvas_point(&self, point: Point2) -> Point2 {
+        match &self.container {
+            Some(container) => pt2(
+                container.left() + point.x * container.w(),
+                container.bottom() + point.y * container.h(),
+            ),
+            None => point,
+        }
+    }
+
+    fn to_canvas_points(&self, points: Path2) -> Path2 {
+        match &self.container {
+            Some(_) => points
+                .into_iter()
+                .map(|point| self.to_canvas_point(point))
+                .collect(),
+            None => points,
+        }
+    }
+
+    fn to_canvas_width(&self, width: NormalizedF32) -> NormalizedF32 {
+        match &self.container {
+            Some(container) => width * container.w(),
+            None => width,
+        }
+    }
+}
+
+fn draw_with_nannou(draw: &Draw, shape: &Shape) {
+    match shape {
+        Shape::Polyline {
+            points,
+            stroke_weight,
+            color,
+        } => {
+            draw.polyline()
+                .stroke_weight(*stroke_weight)
+                .points(points.clone())
+                .color(*color);
+        }
+
+        Shape::Polygon { points, color } => {
+            draw.polygon().points(points.clone()).color(*color);
+        }
+
+        Shape::Ellipse { center, wh, color } => {
+            draw.ellipse().xy(*center).wh(*wh).color(*color);
+        }
+
+        // The draw instance already has the layer's blend mode.
+        Shape::Blended { shape, .. } => draw_with_nannou(draw, shape),
+
+        // Needs a texture, see draw_pixels_with_nannou.
+        Shape::Pixels { .. } => {}
+    }
+}
+
+// The texture covers the whole window, while the draw instance is scaled to the
+// container, so we work out where the window is in normalized coordinates.
+fn draw_pixels_with_nannou(draw: &Draw, texture: &nannou::wgpu::Texture, blend_mode: BlendMode) {
+    let [window_width, window_height] = get_window_dimensions();
+    let container = get_container_rect();
+
+    draw.color_blend(blend_mode.blend_component())
+        .texture(texture)
+        .xy(pt2(
+            -container.left() / container.w(),
+            -container.bottom() / container.h(),
+        ))
+        .wh(vec2(
+            window_width / container.w(),
+            window_height / container.h(),
+        ));
+}
+
+impl BlendMode {
+    // For drawing to a window. The GPU blends each color channel with fixed
+    // factors, so multiply and screen only match the rasterizer for opaque colors,
+    // and darken, lighten and add ignore opacity altogether. Overlay and pigment
+    // mixing can't be done with fixed factors, so they look normal in the window.
+    pub fn blend_component(&self) -> nannou::wgpu::BlendComponent {
+        use nannou::wgpu::{BlendComponent, BlendFactor, BlendOperation};
+
+        let component = |src_factor, dst_factor, operation| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation,
+        };
+
+        match self {
+            BlendMode::Normal | BlendMode::Overlay | BlendMode::PigmentMix => component(
+                BlendFactor::SrcAlpha,
+                BlendFactor::OneMinusSrcAlpha,
+                BlendOperation::Add,
+            ),
+            BlendMode::Multiply => component(
+                BlendFactor::Dst,
+                BlendFactor::OneMinusSrcAlpha,
+                BlendOperation::Add,
+            ),
+            BlendMode::Screen => component(
+                BlendFactor::OneMinusDst,
+                BlendFactor::One,
+                BlendOperation::Add,
+            ),
+            BlendMode::Darken => component(BlendFactor::One, BlendFactor::One, BlendOperation::Min),
+            BlendMode::Lighten => {
+                component(BlendFactor::One, BlendFactor::One, BlendOperation::Max)
+            }
+            BlendMode::Add => {
+                component(BlendFactor::SrcAlpha, BlendFactor::One, BlendOperation::Add)
+            }
+        }
+    }
+
+    pub fn tiny_skia_blend_mode(&self) -> tiny_skia::BlendMode {
+        match self {
+            BlendMode::Normal => tiny_skia::BlendMode::SourceOver,
+            BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+            BlendMode::Screen => tiny_skia::BlendMode::Screen,
+            BlendMode::Darken => tiny_skia::BlendMode::Darken,
+            BlendMode::Lighten => tiny_skia::BlendMode::Lighten,
+            BlendMode::Add => tiny_skia::BlendMode::Plus,
+            BlendMode::Overlay => tiny_skia::BlendMode::Overlay,
+            // The rasterizer mixes these itself.
+            BlendMode::PigmentMix => tiny_skia::BlendMode::SourceOver,
+        }
+    }
+}
+
+pub struct CanvasPolylines<'a> {
+    canvas: &'a Canvas,
+    num_polylines: usize,
+    stroke_weight: NormalizedF32,
+    color: Hsla,
+}
+
+impl<'a> CanvasPolylines<'a> {
+    pub fn stroke_weight(mut self, stroke_weight: NormalizedF32) -> Self {
+        self.stroke_weight = stroke_weight;
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<Hsla>) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn points(self, paths: Vec<Path2>) {
+        for path in paths.into_iter().take(self.num_polylines) {
+            self.canvas.polyline(path, self.stroke_weight, self.color);
+        }
+    }
+}
+
+pub struct CanvasComposite<'a> {
+    canvas: &'a Canvas,
+    layer_name: String,
+    blend_mode: BlendMode,
+    opacity: NormalizedF32,
+    // In whole canvas coordinates, like the canvas's own masks.
+    masks: Vec<Path2>,
+}
+
+impl<'a> CanvasComposite<'a> {
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn opacity(mut self, opacity: NormalizedF32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    // Only the part of the layer inside the mask gets composited. Several masks
+    // keep the part inside all of them.
+    pub fn mask(mut self, mask: Path2) -> Self {
+        self.masks.push(self.canvas.to_canvas_points(mask));
+        self
+    }
+
+    pub fn shell_mask(self, shell: Shell) -> Self {
+        self.mask(shell.into_path())
+    }
+
+    pub fn draw(self) {
+        let layer = self
+            .canvas
+            .offscreen_layers
+            .borrow_mut()
+            .take(&self.layer_name);
+
+        // Nothing has been drawn on it yet.
+        let mut layer = match layer {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        for mask in self.canvas.masks.iter().chain(&self.masks) {
+            layer.apply_mask(mask);
+        }
+        layer.fade(self.opacity);
+
+        self.canvas
+            .add_pixels(&self.layer_name, layer.into_pixmap(), self.blend_mode);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::prelude::*;
+use nannou::wgpu;
+use std::collections::HashMap;
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+// Named pixel buffers that artworks draw into before compositing them onto the
+// canvas, e.g. a multiplied wash or a paper grain overlay. They're rasterized on the
+// CPU even when there's a window, since the GPU can't do most of the blend modes.
+//
+// They last for the whole render, so a layer can build up over several frames
+// before it's composited. Checkpoints save them next to the canvas.
+#[derive(Default)]
+pub struct OffscreenLayers {
+    layers: HashMap<String, Rasterizer>,
+    // For showing composited layers in the window, so we don't make new ones every
+    // frame. Uploads all land before the frame gets drawn, so a layer composited
+    // more than once a frame needs a texture for each time.
+    textures: HashMap<String, Vec<wgpu::Texture>>,
+    num_textures_used: HashMap<String, usize>,
+}
+
+impl OffscreenLayers {
+    // Transparent until something is drawn on it.
+    pub fn layer(&mut self, name: &str) -> &mut Rasterizer {
+        self.layers
+            .entry(name.to_string())
+            .or_insert_with(|| Rasterizer::new(&get_container_rect()))
+    }
+
+    pub fn take(&mut self, name: &str) -> Option<Rasterizer> {
+        self.layers.remove(name)
+    }
+
+    // Sorted, so the names come out the same way every time.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.layers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // PNGs aren't premultiplied, so partly transparent pixels can come back a
+    // rounding off after a round trip.
+    pub fn save_png(&self, name: &str, path: &Path) {
+        if let Some(layer) = self.layers.get(name) {
+            layer.save_png(path);
+        }
+    }
+
+    pub fn load_png(&mut self, name: &str, path: &Path) {
+        self.layer(name).load_png(path);
+    }
+
+    pub fn start_frame(&mut self) {
+        self.num_textures_used.clear();
+    }
+
+    // Uploads the composited layer's pixels to the next of its textures that
+    // hasn't been used this frame.
+    pub fn texture(
+        &mut self,
+        name: &str,
+        device_queue_pair: &wgpu::DeviceQueuePair,
+        pixmap: &Pixmap,
+    ) -> &wgpu::Texture {
+        let device = device_queue_pair.device();
+        let size = [pixmap.width(), pixmap.height()];
+
+        let num_used = self.num_textures_used.entry(name.to_string()).or_insert(0);
+        let index = *num_used;
+        *num_used += 1;
+
+        let textures = self.textures.entry(name.to_string()).or_default();
+        if index == textures.len() || textures[index].size() != size {
+            let texture = wgpu::TextureBuilder::new()
+                .size(size)
+                .format(wgpu::TextureFormat::Rgba8UnormSrgb)
+                .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+                .build(device);
+
+            if index == textures.len() {
+                textures.push(texture);
+            } else {
+                textures[index] = texture;
+            }
+        }
+
+        let texture = &textures[index];
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("offscreen layer upload"),
+        });
+        texture.upload_data(device, &mut encoder, &demultiplied_bytes(pixmap));
+        device_queue_pair.queue().submit(Some(encoder.finish()));
+
+        texture
+    }
+}
+
+// Textures want straight alpha, while pixmaps are premultiplied.
+fn demultiplied_bytes(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let pixel = pixel.demultiply();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn composites_multiplied_layer_only_inside_mask() {
+        let container = get_container_rect();
+        let canvas = Canvas::headless();
+
+        let whole_canvas = vec![pt2(0.0, 0.0), pt2(1.0, 0.0), pt2(1.0, 1.0), pt2(0.0, 1.0)];
+        let left_half = vec![pt2(0.0, 0.0), pt2(0.5, 0.0), pt2(0.5, 1.0), pt2(0.0, 1.0)];
+
+        canvas
+            .offscreen("wash")
+            .polygon(whole_canvas, hsl(0.0, 0.0, 0.5));
+        canvas
+            .composite("wash")
+            .blend_mode(BlendMode::Multiply)
+            .mask(left_half)
+            .draw();
+
+        let mut rasterizer = Rasterizer::new(&container);
+        rasterizer.fill_background(hsl(0.0, 0.0, 1.0));
+        rasterizer.rasterize(&canvas.take_recorded_shapes());
+        let image = rasterizer.to_rgba_image();
+
+        let [window_width, window_height] = get_window_dimensions();
+        let pixel_at = |point: Point2| {
+            let x = container.left() + point.x * container.w() + window_width / 2.0;
+            let y = window_height / 2.0 - (container.bottom() + point.y * container.h());
+            image.get_pixel(x as u32, y as u32).0
+        };
+
+        assert_eq!(pixel_at(pt2(0.75, 0.5)), [255, 255, 255, 255]);
+
+        let [red, green, blue, _] = pixel_at(pt2(0.25, 0.5));
+        assert!(red < 200 && red == green && green == blue);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use nannou::color::{FromColor, Srgb};
+use nannou::image::{Rgba, RgbaImage};
+use std::path::Path;
+use tiny_skia::{
+    ColorU8, FillRule, Mask, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform,
+};
+
+// Draws recorded shapes into a CPU-side pixel buffer the same size as the window.
+// Used when there's no window (and no GPU) to draw to.
+pub struct Rasterizer {
+    pixmap: Pixmap,
+    normalized_to_pixels: Transform,
+}
+
+impl Rasterizer {
+    pub fn new(container: &DenormalizedRect) -> Rasterizer {
+        let [window_width, window_height] = get_window_dimensions();
+
+        let pixmap = Pixmap::new(window_width as u32, window_height as u32)
+            .expect("Couldn't create a pixmap with the window dimensions.");
+
+        // Shapes are recorded in normalized container coordinates, with y pointing up.
+        // Pixels start in the top left corner, with y pointing down.
+        let normalized_to_pixels = Transform::from_row(
+            container.w(),
+            0.0,
+            0.0,
+            -container.h(),
+            container.left() + window_width / 2.0,
+            window_height / 2.0 - container.bottom(),
+        );
+
+        Rasterizer {
+            pixmap,
+            normalized_to_pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixmap.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixmap.height()
+    }
+
+    pub fn fill_background(&mut self, color: Hsl) {
+        self.pixmap.fill(tiny_skia_color(color.into()));
+    }
+
+    pub fn rasterize(&mut self, shapes: &[Shape]) {
+        for shape in shapes {
+            self.rasterize_shape(shape, BlendMode::Normal);
+        }
+    }
+
+    // Only keeps the pixels inside the mask, which is in normalized container
+    // coordinates. The edges are antialiased, so they fade out instead of being cut.
+    pub fn apply_mask(&mut self, mask: &Path2) {
+        let mut alpha_mask = Mask::new(self.width(), self.height()).unwrap();
+
+        if let Some(path) = tiny_skia_path(mask, true) {
+            alpha_mask.fill_path(&path, FillRule::Winding, true, self.normalized_to_pixels);
+        }
+
+        self.pixmap.apply_mask(&alpha_mask);
+    }
+
+    // Scales every pixel's opacity. The pixels are premultiplied, so the color
+    // channels get scaled along with the alpha.
+    pub fn fade(&mut self, opacity: NormalizedF32) {
+        if opacity >= 1.0 {
+            return;
+        }
+
+        let opacity = opacity.clamp(0.0, 1.0);
+        for channel in self.pixmap.data_mut() {
+            *channel = (*channel as f32 * opacity).round() as u8;
+        }
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.pixmap
+    }
+
+    // The background is opaque, so this round trips exactly.
+    pub fn save_png(&self, path: &Path) {
+        self.pixmap
+            .save_png(path)
+            .expect("Couldn't save the canvas as a PNG.");
+    }
+
+    pub fn load_png(&mut self, path: &Path) {
+        let pixmap = Pixmap::load_png(path).expect("Couldn't load the canvas from a PNG.");
+
+        if pixmap.width() != self.width() || pixmap.height() != self.height() {
+            panic!("The saved canvas is a different size from the window.");
+        }
+
+        self.pixmap = pixmap;
+    }
+
+    pub fn to_rgba_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width(), self.height(), |x, y| {
+            let pixel = self.pixmap.pixel(x, y).unwrap().demultiply();
+            Rgba([pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()])
+        })
+    }
+
+    fn rasterize_shape(&mut self, shape: &Shape, blend_mode: BlendMode) {
+        match shape {
+            Shape::Polyline {
+                points,
+                stroke_weight,
+                color,
+            } => {
+                let path = match tiny_skia_path(points, false) {
+                    Some(path) => path,
+                    None => return,
+                };
+
+                let stroke = Stroke {
+                    width: *stroke_weight,
+                    ..Stroke::default()
+                };
+
+                self.pixmap.stroke_path(
+                    &path,
+                    &paint(*color, blend_mode),
+                    &stroke,
+                    self.normalized_to_pixels,
+                    None,
+                );
+            }
+
+            Shape::Polygon { points, color } => {
+                let path = match tiny_skia_path(points, true) {
+                    Some(path) => path,
+                    None => return,
+                };
+
+                self.pixmap.fill_path(
+                    &path,
+                    &paint(*color, blend_mode),
+                    FillRule::Winding,
+                    self.normalized_to_pixels,
+                    None,
+                );
+            }
+
+            Shape::Ellipse { center, wh, color } => {
+                let oval = tiny_skia::Rect::from_xywh(
+                    center.x - wh.x / 2.0,
+                    center.y - wh.y / 2.0,
+                    wh.x,
+                    wh.y,
+                );
+
+                let path = match oval.and_then(PathBuilder::from_oval) {
+                    Some(path) => path,
+                    None => return,
+                };
+
+                self.pixmap.fill_path(
+                    &path,
+                    &paint(*color, blend_mode),
+                    FillRule::Winding,
+                    self.normalized_to_pixels,
+                    None,
+                );
+            }
+
+            // Pigments can't be mixed by tiny-skia, so the shape gets a pixmap of its own
+            // first. That's a whole window of pixels per shape, so it's slow.
+            Shape::Blended {
+                shape,
+                blend_mode: BlendMode::PigmentMix,
+            } => {
+                let mut shape_only = Rasterizer {
+                    pixmap: Pixmap::new(self.width(), self.height()).unwrap(),
+                    normalized_to_pixels: self.normalized_to_pixels,
+                };
+                shape_only.rasterize_shape(shape, BlendMode::Normal);
+
+                mix_pigments(&mut self.pixmap, &shape_only.pixmap);
+            }
+
+            Shape::Blended { shape, blend_mode } => self.rasterize_shape(shape, *blend_mode),
+
+            Shape::Pixels { pixmap, blend_mode } => self.composite(pixmap, *blend_mode),
+        }
+    }
+
+    // The layer's pixmap is the same size as ours, and lines up with it.
+    fn composite(&mut self, layer: &Pixmap, blend_mode: BlendMode) {
+        match blend_mode {
+            BlendMode::PigmentMix => mix_pigments(&mut self.pixmap, layer),
+            blend_mode => {
+                let paint = PixmapPaint {
+                    blend_mode: blend_mode.tiny_skia_blend_mode(),
+                    ..PixmapPaint::default()
+                };
+
+                self.pixmap
+                    .draw_pixmap(0, 0, layer.as_ref(), &paint, Transform::identity(), None);
+            }
+        }
+    }
+}
+
+// Mixes each of the layer's colors into the pixel underneath like paint, using the
+// layer's opacity as the amount. Blue and yellow make green instead of gray.
+fn mix_pigments(base: &mut Pixmap, layer: &Pixmap) {
+    for (base_pixel, layer_pixel) in base.pixels_mut().iter_mut().zip(layer.pixels()) {
+        if layer_pixel.alpha() == 0 {
+            continue;
+        }
+
+        let base_color = base_pixel.demultiply();
+        let layer_color = layer_pixel.demultiply();
+
+        // There's no paint underneath to mix with.
+        if base_color.alpha() == 0 {
+            *base_pixel = *layer_pixel;
+            continue;
+        }
+
+        let amount = layer_color.alpha() as f32 / 255.0;
+        let mixed = srgb8(base_color.red(), base_color.green(), base_color.blue()).mix_pigment(
+            &srgb8(layer_color.red(), layer_color.green(), layer_color.blue()),
+            amount,
+        );
+
+        let alpha = base_color.alpha() as f32
+            + layer_color.alpha() as f32 * (1.0 - base_color.alpha() as f32 / 255.0);
+
+        *base_pixel = ColorU8::from_rgba(mixed.red, mixed.green, mixed.blue, alpha.round() as u8)
+            .premultiply();
+    }
+}
+
+fn tiny_skia_path(points: &Path2, is_closed: bool) -> Option<tiny_skia::Path> {
+    let (first, rest) = points.split_first()?;
+
+    let mut path_builder = PathBuilder::with_capacity(points.len() + 1, points.len());
+    path_builder.move_to(first.x, first.y);
+
+    for point in rest {
+        path_builder.line_to(point.x, point.y);
+    }
+
+    if is_closed {
+        path_builder.close();
+    }
+
+    path_builder.finish()
+}
+
+fn paint(color: Hsla, blend_mode: BlendMode) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(tiny_skia_color(color));
+    paint.anti_alias = true;
+    paint.blend_mode = blend_mode.tiny_skia_blend_mode();
+    paint
+}
+
+pub fn tiny_skia_color(color: Hsla) -> tiny_skia::Color {
+    let srgb: Srgb = Srgb::from_hsl(color.color);
+    let (red, green, blue) = srgb.into_components();
+
+    tiny_skia::Color::from_rgba(
+        red.clamp(0.0, 1.0),
+        green.clamp(0.0, 1.0),
+        blue.clamp(0.0, 1.0),
+        color.alpha.clamp(0.0, 1.0),
+    )
+    .unwrap()
+}
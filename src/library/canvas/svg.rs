@@ -0,0 +1,216 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+pub fn is_enabled() -> bool {
+    std::env::args().any(|argument| argument == "--svg")
+}
+
+// Reorders and merges polylines so a pen plotter spends less time with the pen up.
+pub fn should_optimize_paths() -> bool {
+    std::env::args().any(|argument| argument == "--optimize-svg")
+}
+
+// Writes the recorded shapes as an SVG the same size as the window, measured in
+// millimeters so that it can go straight to a pen plotter. We leave the background
+// out, since a plotter would try to draw it.
+pub fn save(shapes: &[Shape], path: &Path) {
+    fs::write(path, to_svg(shapes)).expect("Couldn't save the SVG.");
+}
+
+pub fn to_svg(shapes: &[Shape]) -> String {
+    let [window_width, window_height] = get_window_dimensions();
+    let to_millimeters = ToMillimeters::new(&get_container_rect());
+
+    let page_width = window_width * millimeters_per_pixel();
+    let page_height = window_height * millimeters_per_pixel();
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{page_width}mm" height="{page_height}mm" viewBox="0 0 {page_width} {page_height}">"#
+    )
+    .unwrap();
+
+    if should_optimize_paths() {
+        write_optimized_shapes(&mut svg, shapes, &to_millimeters);
+    } else {
+        for shape in shapes {
+            writeln!(svg, "  {}", svg_element(shape, &to_millimeters)).unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+// Fills come first. Then polylines get one group per pen, i.e. per color and
+// stroke weight, optimized separately so each pen can be plotted in one go.
+fn write_optimized_shapes(svg: &mut String, shapes: &[Shape], to_millimeters: &ToMillimeters) {
+    let mut polylines_by_pen = vec![];
+
+    for shape in shapes {
+        match shape {
+            Shape::Polyline {
+                points,
+                stroke_weight,
+                color,
+            } => {
+                let pen = Pen {
+                    color: *color,
+                    stroke_weight: *stroke_weight,
+                };
+                polylines_by_pen.push((pen.key(), (pen, points.clone())));
+            }
+            _ => writeln!(svg, "  {}", svg_element(shape, to_millimeters)).unwrap(),
+        }
+    }
+
+    let mut pens = HashMap::new();
+    let paths_by_pen = polylines_by_pen
+        .into_iter()
+        .map(|(pen_key, (pen, points))| {
+            pens.insert(pen_key.clone(), pen);
+            (pen_key, points)
+        })
+        .collect();
+
+    let optimized_layers = path_optimizer().optimize_layers(paths_by_pen);
+
+    for (layer_index, (pen_key, optimized_paths)) in optimized_layers.into_iter().enumerate() {
+        let pen = pens.get(&pen_key).unwrap();
+        writeln!(svg, r#"  <g id="pen-{layer_index}">"#).unwrap();
+
+        for points in optimized_paths.paths {
+            let shape = Shape::Polyline {
+                points,
+                stroke_weight: pen.stroke_weight,
+                color: pen.color,
+            };
+            writeln!(svg, "    {}", svg_element(&shape, to_millimeters)).unwrap();
+        }
+
+        writeln!(svg, "  </g>").unwrap();
+    }
+}
+
+struct Pen {
+    color: Hsla,
+    stroke_weight: NormalizedF32,
+}
+
+impl Pen {
+    fn key(&self) -> String {
+        format!(
+            "{} at {} opacity, {} wide",
+            svg_color(&self.color),
+            self.color.alpha,
+            self.stroke_weight
+        )
+    }
+}
+
+fn svg_element(shape: &Shape, to_millimeters: &ToMillimeters) -> String {
+    match shape {
+        Shape::Polyline {
+            points,
+            stroke_weight,
+            color,
+        } => {
+            let stroke_width = to_millimeters.width(*stroke_weight);
+            format!(
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{stroke_width}" stroke-linecap="round" stroke-linejoin="round" />"#,
+                to_millimeters.points(points),
+                svg_color(color),
+                color.alpha,
+            )
+        }
+
+        Shape::Polygon { points, color } => {
+            format!(
+                r#"<polygon points="{}" fill="{}" fill-opacity="{}" />"#,
+                to_millimeters.points(points),
+                svg_color(color),
+                color.alpha,
+            )
+        }
+
+        Shape::Ellipse { center, wh, color } => {
+            let center = to_millimeters.point(center);
+            let radius_x = to_millimeters.width(wh.x / 2.0);
+            let radius_y = to_millimeters.height(wh.y / 2.0);
+            format!(
+                r#"<ellipse cx="{}" cy="{}" rx="{radius_x}" ry="{radius_y}" fill="{}" fill-opacity="{}" />"#,
+                center.x,
+                center.y,
+                svg_color(color),
+                color.alpha,
+            )
+        }
+
+        // Plotters can't blend, so we leave the blend mode out.
+        Shape::Blended { shape, .. } => svg_element(shape, to_millimeters),
+
+        // Nor draw pixels.
+        Shape::Pixels { .. } => "<!-- An offscreen layer, left out. -->".to_string(),
+    }
+}
+
+fn svg_color(color: &Hsla) -> String {
+    let srgb8 = color.color.as_srgb8();
+    format!("rgb({}, {}, {})", srgb8.red, srgb8.green, srgb8.blue)
+}
+
+// Converts normalized container coordinates (y pointing up) into millimeters on
+// the page (y pointing down).
+struct ToMillimeters {
+    container: DenormalizedRect,
+    window_width: f32,
+    window_height: f32,
+    millimeters_per_pixel: f32,
+}
+
+impl ToMillimeters {
+    fn new(container: &DenormalizedRect) -> ToMillimeters {
+        let [window_width, window_height] = get_window_dimensions();
+
+        ToMillimeters {
+            container: *container,
+            window_width,
+            window_height,
+            millimeters_per_pixel: millimeters_per_pixel(),
+        }
+    }
+
+    fn point(&self, point: &Point2) -> Point2 {
+        let pixel_x =
+            self.container.left() + point.x * self.container.w() + self.window_width / 2.0;
+        let pixel_y =
+            self.window_height / 2.0 - (self.container.bottom() + point.y * self.container.h());
+
+        pt2(pixel_x, pixel_y).times(self.millimeters_per_pixel)
+    }
+
+    fn points(&self, points: &Path2) -> String {
+        points
+            .iter()
+            .map(|point| {
+                let point = self.point(point);
+                format!("{},{}", point.x, point.y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn width(&self, normalized_width: NormalizedF32) -> f32 {
+        normalized_width * self.container.w() * self.millimeters_per_pixel
+    }
+
+    fn height(&self, normalized_height: NormalizedF32) -> f32 {
+        normalized_height * self.container.h() * self.millimeters_per_pixel
+    }
+}
@@ -0,0 +1,210 @@
+use crate::prelude::*;
+use crate::snapshot::manifest;
+use crate::snapshot::rand::RandState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Everything we need to pick a long render back up after a crash or a closed
+// window. Saved in checkpoints/<friendly_name>/, next to PNGs of the canvas and any
+// offscreen layers. The PNGs have to be saved first, so the JSON never points at
+// one that isn't all there.
+//
+// The artwork itself gets created from scratch when we resume, so artworks that
+// build up state between draws should implement Artwork::checkpoint_state.
+// Otherwise they'll carry on from the saved canvas with fresh state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub snapshot_name: String,
+    pub num_drawn: usize,
+    pub scheduling: DrawScheduling,
+    // The rand that changes every frame. The one that stays the same every frame
+    // gets recreated from the seed, so there's nothing to save.
+    pub rand_state: RandState,
+    pub artwork_state: Option<serde_json::Value>,
+    // So we resume with the values from the panel, not the defaults.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, ParameterValue>,
+    // Each checkpoint gets its own, so saving a new canvas can't spoil the one the
+    // last checkpoint points at.
+    #[serde(default = "default_canvas_file_name")]
+    pub canvas_file_name: String,
+    // The offscreen layers that hadn't been composited yet, by layer name.
+    #[serde(default)]
+    pub offscreen_layer_file_names: BTreeMap<String, String>,
+}
+
+// What checkpoints were called before they had their own.
+fn default_canvas_file_name() -> String {
+    "canvas.png".to_string()
+}
+
+// Why an artwork couldn't carry on from its checkpoint_state.
+#[derive(Debug)]
+pub enum CheckpointStateError {
+    Json(serde_json::Error),
+    NumLayersChanged { num_saved: usize, num_layers: usize },
+}
+
+impl fmt::Display for CheckpointStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointStateError::Json(error) => {
+                write!(
+                    f,
+                    "Couldn't restore the artwork's checkpoint state: {error}"
+                )
+            }
+            CheckpointStateError::NumLayersChanged {
+                num_saved,
+                num_layers,
+            } => write!(
+                f,
+                "The checkpoint has {num_saved} layers, but the artwork has {num_layers} now."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointStateError {}
+
+impl From<serde_json::Error> for CheckpointStateError {
+    fn from(error: serde_json::Error) -> Self {
+        CheckpointStateError::Json(error)
+    }
+}
+
+impl Checkpoint {
+    // --resume <friendly_name>
+    pub fn from_command_line() -> Option<Checkpoint> {
+        let snapshot_name = command_line::value_of("--resume")?;
+        let checkpoint = Checkpoint::load(&snapshot_name)
+            .unwrap_or_else(|| panic!("Couldn't find a checkpoint for {snapshot_name}."));
+
+        Some(checkpoint)
+    }
+
+    pub fn load(snapshot_name: &str) -> Option<Checkpoint> {
+        let json = fs::read_to_string(json_path(snapshot_name)).ok()?;
+        let checkpoint = serde_json::from_str(&json).unwrap_or_else(|error| {
+            panic!("Couldn't parse the checkpoint for {snapshot_name}: {error}")
+        });
+
+        Some(checkpoint)
+    }
+
+    pub fn canvas_file_name(num_drawn: usize) -> String {
+        format!("canvas_{num_drawn}.png")
+    }
+
+    // Call this once the canvas is saved. The JSON gets swapped in with a rename, so
+    // a crash leaves either the old checkpoint or the new one.
+    pub fn save(&self) {
+        let json = serde_json::to_string_pretty(self).expect("Couldn't serialize the checkpoint.");
+        let temporary_path = json_path(&self.snapshot_name).with_extension("json.tmp");
+        fs::write(&temporary_path, json).expect("Couldn't save the checkpoint.");
+        fs::rename(&temporary_path, json_path(&self.snapshot_name))
+            .expect("Couldn't save the checkpoint.");
+
+        self.remove_old_canvases();
+    }
+
+    // Layer names can have anything in them, so the files are numbered instead.
+    pub fn offscreen_layer_file_name(layer_index: usize, num_drawn: usize) -> String {
+        format!("layer_{layer_index}_{num_drawn}.png")
+    }
+
+    pub fn canvas_path(&self) -> PathBuf {
+        self.file_path(&self.canvas_file_name)
+    }
+
+    pub fn offscreen_layer_paths(&self) -> Vec<(String, PathBuf)> {
+        self.offscreen_layer_file_names
+            .iter()
+            .map(|(layer_name, file_name)| (layer_name.clone(), self.file_path(file_name)))
+            .collect()
+    }
+
+    // Also makes sure the folder exists, so the PNGs can be saved before the JSON.
+    fn file_path(&self, file_name: &str) -> PathBuf {
+        let folder = folder(&self.snapshot_name);
+        fs::create_dir_all(&folder).expect("Couldn't create the checkpoint folder.");
+
+        folder.join(file_name)
+    }
+
+    fn remove_old_canvases(&self) {
+        let entries = fs::read_dir(folder(&self.snapshot_name))
+            .expect("Couldn't read the checkpoint folder.");
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let is_current = file_name == self.canvas_file_name
+                || self
+                    .offscreen_layer_file_names
+                    .values()
+                    .any(|layer_file_name| *layer_file_name == file_name);
+
+            if file_name.ends_with(".png") && !is_current {
+                fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+
+    // Once the render finishes, the checkpoint isn't useful anymore.
+    pub fn remove(snapshot_name: &str) {
+        let folder = folder(snapshot_name);
+        if folder.exists() {
+            fs::remove_dir_all(folder).expect("Couldn't remove the checkpoint.");
+        }
+    }
+}
+
+// Decides when it's time to save another checkpoint.
+pub struct Checkpointer {
+    pub snapshot_name: String,
+    interval: Duration,
+    last_checkpoint: Instant,
+}
+
+impl Checkpointer {
+    // Checkpoints every minute by default. --checkpoint-every <seconds> changes that.
+    pub fn from_command_line(snapshot_name: &str) -> Checkpointer {
+        let interval_seconds = command_line::value_of("--checkpoint-every")
+            .map(|value| {
+                value.parse::<u64>().unwrap_or_else(|_| {
+                    panic!("--checkpoint-every expects a number of seconds, got {value}.")
+                })
+            })
+            .unwrap_or(60);
+
+        Checkpointer::new(snapshot_name, Duration::from_secs(interval_seconds))
+    }
+
+    pub fn new(snapshot_name: &str, interval: Duration) -> Checkpointer {
+        Checkpointer {
+            snapshot_name: snapshot_name.to_string(),
+            interval,
+            last_checkpoint: Instant::now(),
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_checkpoint.elapsed() >= self.interval
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.last_checkpoint = Instant::now();
+    }
+}
+
+fn folder(snapshot_name: &str) -> PathBuf {
+    manifest::folder().join("checkpoints").join(snapshot_name)
+}
+
+fn json_path(snapshot_name: &str) -> PathBuf {
+    folder(snapshot_name).join("checkpoint.json")
+}
@@ -0,0 +1,68 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A color in a config file, e.g. an artwork config or a post-processing palette.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ColorConfig {
+    // Like #fca297.
+    Hex(String),
+    // The hue is normalized, like the hsl function expects.
+    Hsl {
+        hue: f32,
+        saturation: f32,
+        lightness: f32,
+    },
+}
+
+#[derive(Debug)]
+pub enum ColorConfigError {
+    InvalidHex(String),
+}
+
+impl fmt::Display for ColorConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorConfigError::InvalidHex(color) => {
+                write!(f, "{color} isn't a color. Try something like #fca297.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColorConfigError {}
+
+impl ColorConfig {
+    pub fn to_hsl(&self) -> Result<Hsl, ColorConfigError> {
+        match self {
+            ColorConfig::Hsl {
+                hue,
+                saturation,
+                lightness,
+            } => Ok(hsl(*hue, *saturation, *lightness)),
+            ColorConfig::Hex(hex) => {
+                let invalid = || ColorConfigError::InvalidHex(hex.clone());
+
+                let digits = hex.strip_prefix('#').unwrap_or(hex);
+                if digits.len() != 6 {
+                    return Err(invalid());
+                }
+
+                let channel = |index: usize| {
+                    digits
+                        .get(index..index + 2)
+                        .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                        .ok_or_else(invalid)
+                };
+
+                Ok(Colors::color_picker(
+                    channel(0)?,
+                    channel(2)?,
+                    channel(4)?,
+                    255,
+                ))
+            }
+        }
+    }
+}
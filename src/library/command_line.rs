@@ -0,0 +1,29 @@
+// Small helpers for reading command line flags, e.g. `--replay <name>`.
+
+pub fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|argument| argument == flag)
+}
+
+// The argument right after the flag, if the flag was passed.
+pub fn value_of(flag: &str) -> Option<String> {
+    values_of(flag, 1).map(|mut values| values.remove(0))
+}
+
+// The next num_values arguments after the flag. Panics if there aren't enough, since
+// that's almost certainly a typo.
+pub fn values_of(flag: &str, num_values: usize) -> Option<Vec<String>> {
+    let arguments: Vec<String> = std::env::args().collect();
+    let flag_index = arguments.iter().position(|argument| argument == flag)?;
+
+    let values: Vec<String> = arguments
+        .into_iter()
+        .skip(flag_index + 1)
+        .take(num_values)
+        .collect();
+
+    if values.len() < num_values {
+        panic!("{flag} expects {num_values} value(s) after it.");
+    }
+
+    Some(values)
+}
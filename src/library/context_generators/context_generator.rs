@@ -17,3 +17,25 @@ where
         self.context_provider.generate(rand, input)
     }
 }
+
+// Lets generators built at runtime, e.g. from an artwork config, be mapped and
+// crystallized like any other generator.
+impl<Output, Context> GeneratorHeart<(), Output, Context> for ContextGenerator<(), Output>
+where
+    Output: Clone + 'static,
+    Context: Sized + 'static,
+{
+    fn generate_with_context(&mut self, params: &GenerateWithContextParams<(), Context>) -> Output {
+        self.generate(params.rand, ())
+    }
+}
+
+impl<Input, Output> IntoContextGenerator<Input, Output> for ContextGenerator<Input, Output>
+where
+    Input: 'static,
+    Output: Clone + 'static,
+{
+    fn into_context_generator(self) -> ContextGenerator<Input, Output> {
+        self
+    }
+}
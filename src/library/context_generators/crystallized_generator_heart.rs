@@ -40,9 +40,7 @@ where
         &mut self,
         params: &GenerateWithContextParams<Input, Context>,
     ) -> Output {
-        let current_draw_index = *crate::library::loop_drawer::global_draw_index
-            .lock()
-            .unwrap();
+        let current_draw_index = *crate::library::loop_drawer::draw_index().lock().unwrap();
 
         let draw_index_has_incremented = current_draw_index > self.last_draw_index;
         let should_update = draw_index_has_incremented || self.previous_output.is_none();
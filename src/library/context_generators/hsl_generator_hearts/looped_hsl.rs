@@ -18,6 +18,11 @@ impl LoopedHsl {
         self.colors.push(new_color);
         self
     }
+
+    pub fn color(mut self, color: Hsl) -> Self {
+        self.colors.push(color);
+        self
+    }
 }
 
 impl<Context> GeneratorHeart<(), Hsl, Context> for LoopedHsl
@@ -0,0 +1,238 @@
+use super::fractal_noise::{hash_lattice, unit_f64};
+use super::rand_streams::{hash_name, mix};
+use crate::prelude::*;
+use crate::snapshot::rand::Rand;
+
+// Simplex noise that also returns its exact gradient, so we can take its curl
+// without nudging points around and estimating derivatives.
+impl Rand {
+    // The value goes from roughly -1 to 1.
+    pub fn simplex_xy_with_gradient(&self, point: &Point2) -> (f32, Vec2) {
+        let key = self.simplex_key("simplex 2d");
+        let (value, gradient) = simplex_2d(key, point.x as f64, point.y as f64);
+
+        (value as f32, vec2(gradient[0] as f32, gradient[1] as f32))
+    }
+
+    pub fn simplex_xyz_with_gradient(&self, point: &Point3) -> (f32, Vec3) {
+        let key = self.simplex_key("simplex 3d");
+        let (value, gradient) = simplex_3d(key, [point.x as f64, point.y as f64, point.z as f64]);
+
+        (value as f32, to_vec3(gradient))
+    }
+
+    // The gradient of simplex noise turned a quarter turn. It has no divergence, so
+    // anything following it swirls around without ever bunching up or spreading
+    // out. The length varies smoothly, and is 0 at the noise's peaks and valleys.
+    pub fn curl_xy(&self, point: &Point2) -> Vec2 {
+        let (_value, gradient) = self.simplex_xy_with_gradient(point);
+        gradient.perpendicular_clockwise()
+    }
+
+    pub fn curl_angle_xy(&self, point: &Point2) -> NumberOfTurns {
+        self.curl_xy(point).normalized_angle()
+    }
+
+    // In 3D we need a vector potential, so we use three unrelated noise fields,
+    // one for each axis.
+    pub fn curl_xyz(&self, point: &Point3) -> Vec3 {
+        let point = [point.x as f64, point.y as f64, point.z as f64];
+
+        let (_, x) = simplex_3d(self.simplex_key("curl x"), point);
+        let (_, y) = simplex_3d(self.simplex_key("curl y"), point);
+        let (_, z) = simplex_3d(self.simplex_key("curl z"), point);
+
+        to_vec3([z[1] - y[2], x[2] - z[0], y[0] - x[1]])
+    }
+
+    fn simplex_key(&self, name: &str) -> u64 {
+        mix(self.seed() ^ hash_name(name))
+    }
+}
+
+fn to_vec3(vector: [f64; 3]) -> Vec3 {
+    vec3(vector[0] as f32, vector[1] as f32, vector[2] as f32)
+}
+
+// Based on Stefan Gustavson's simplex noise with analytic derivatives. Each corner
+// of the simplex contributes falloff⁴ * (gradient · offset), and we differentiate
+// that with the product rule.
+fn simplex_2d(key: u64, x: f64, y: f64) -> (f64, [f64; 2]) {
+    let skew_factor = 0.5 * (3.0_f64.sqrt() - 1.0);
+    let unskew_factor = (3.0 - 3.0_f64.sqrt()) / 6.0;
+
+    let skew = (x + y) * skew_factor;
+    let i = (x + skew).floor();
+    let j = (y + skew).floor();
+
+    let unskew = (i + j) * unskew_factor;
+    let x0 = x - (i - unskew);
+    let y0 = y - (j - unskew);
+
+    // Which of the two triangles in the skewed cell we're in.
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+    let corners = [
+        (0, 0, x0, y0),
+        (
+            i1,
+            j1,
+            x0 - i1 as f64 + unskew_factor,
+            y0 - j1 as f64 + unskew_factor,
+        ),
+        (
+            1,
+            1,
+            x0 - 1.0 + 2.0 * unskew_factor,
+            y0 - 1.0 + 2.0 * unskew_factor,
+        ),
+    ];
+
+    let mut value = 0.0;
+    let mut gradient = [0.0; 2];
+
+    for (di, dj, dx, dy) in corners {
+        let falloff = 0.5 - dx * dx - dy * dy;
+        if falloff <= 0.0 {
+            continue;
+        }
+
+        let hash = hash_lattice(key, &[i as i64 + di, j as i64 + dj]);
+        let angle = unit_f64(hash) * std::f64::consts::TAU;
+        let corner_gradient = [angle.cos(), angle.sin()];
+
+        let dot = corner_gradient[0] * dx + corner_gradient[1] * dy;
+        let falloff_squared = falloff * falloff;
+        let falloff_to_the_fourth = falloff_squared * falloff_squared;
+
+        value += falloff_to_the_fourth * dot;
+
+        let chain = -8.0 * falloff_squared * falloff * dot;
+        gradient[0] += chain * dx + falloff_to_the_fourth * corner_gradient[0];
+        gradient[1] += chain * dy + falloff_to_the_fourth * corner_gradient[1];
+    }
+
+    // Brings the value to roughly -1 to 1.
+    let scale = 70.0;
+    (value * scale, gradient.map(|component| component * scale))
+}
+
+fn simplex_3d(key: u64, point: [f64; 3]) -> (f64, [f64; 3]) {
+    let skew_factor = 1.0 / 3.0;
+    let unskew_factor = 1.0 / 6.0;
+
+    let skew = point.iter().sum::<f64>() * skew_factor;
+    let cell = point.map(|coordinate| (coordinate + skew).floor());
+
+    let unskew = cell.iter().sum::<f64>() * unskew_factor;
+    let first_offset = [
+        point[0] - (cell[0] - unskew),
+        point[1] - (cell[1] - unskew),
+        point[2] - (cell[2] - unskew),
+    ];
+
+    // Walk from the cell's origin to its far corner, one axis at a time, taking the
+    // axis we're furthest along first. That picks which of the six tetrahedra in
+    // the cell we're in.
+    let mut axes = [0, 1, 2];
+    axes.sort_by(|a, b| first_offset[*b].total_cmp(&first_offset[*a]));
+
+    let mut corner_steps = [[0_i64; 3]; 4];
+    for (step, axis) in axes.iter().enumerate() {
+        for later_step in corner_steps.iter_mut().skip(step + 1) {
+            later_step[*axis] = 1;
+        }
+    }
+
+    let mut value = 0.0;
+    let mut gradient = [0.0; 3];
+
+    for (index, steps) in corner_steps.iter().enumerate() {
+        let offset = [
+            first_offset[0] - steps[0] as f64 + index as f64 * unskew_factor,
+            first_offset[1] - steps[1] as f64 + index as f64 * unskew_factor,
+            first_offset[2] - steps[2] as f64 + index as f64 * unskew_factor,
+        ];
+
+        let falloff = 0.6
+            - offset
+                .iter()
+                .map(|component| component * component)
+                .sum::<f64>();
+        if falloff <= 0.0 {
+            continue;
+        }
+
+        let lattice_point = [
+            cell[0] as i64 + steps[0],
+            cell[1] as i64 + steps[1],
+            cell[2] as i64 + steps[2],
+        ];
+        let corner_gradient = gradients_3d[(hash_lattice(key, &lattice_point) % 12) as usize];
+
+        let dot: f64 = (0..3)
+            .map(|axis| corner_gradient[axis] * offset[axis])
+            .sum();
+        let falloff_squared = falloff * falloff;
+        let falloff_to_the_fourth = falloff_squared * falloff_squared;
+
+        value += falloff_to_the_fourth * dot;
+
+        let chain = -8.0 * falloff_squared * falloff * dot;
+        for axis in 0..3 {
+            gradient[axis] += chain * offset[axis] + falloff_to_the_fourth * corner_gradient[axis];
+        }
+    }
+
+    // Brings the value to roughly -1 to 1.
+    let scale = 32.0;
+    (value * scale, gradient.map(|component| component * scale))
+}
+
+// The edges of a cube, as in Perlin's improved noise.
+const gradients_3d: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    // If the gradients weren't exact, the curl would have some divergence.
+    #[test]
+    fn curl_noise_has_no_divergence() {
+        let rand = Rand::from_seed(7);
+        let nudge = 0.003;
+
+        for _ in 0..100 {
+            let point = rand.xy().times(4.0);
+            let divergence = (rand.curl_xy(&(point + vec2(nudge, 0.0))).x
+                - rand.curl_xy(&(point - vec2(nudge, 0.0))).x
+                + rand.curl_xy(&(point + vec2(0.0, nudge))).y
+                - rand.curl_xy(&(point - vec2(0.0, nudge))).y)
+                / (2.0 * nudge);
+            assert!(divergence.abs() < 0.2, "{divergence} at {point}");
+
+            let point = rand.xyz().times(4.0);
+            let divergence = (rand.curl_xyz(&(point + vec3(nudge, 0.0, 0.0))).x
+                - rand.curl_xyz(&(point - vec3(nudge, 0.0, 0.0))).x
+                + rand.curl_xyz(&(point + vec3(0.0, nudge, 0.0))).y
+                - rand.curl_xyz(&(point - vec3(0.0, nudge, 0.0))).y
+                + rand.curl_xyz(&(point + vec3(0.0, 0.0, nudge))).z
+                - rand.curl_xyz(&(point - vec3(0.0, 0.0, nudge))).z)
+                / (2.0 * nudge);
+            assert!(divergence.abs() < 0.2, "{divergence} at {point}");
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use crate::prelude::*;
+use crate::snapshot::rand::Rand;
+use std::f64::consts::{PI, TAU};
+
+// Everything here is built on zero_to_one_f64, so unlike standard_gaussian and
+// friends, the results aren't limited to 65,536 distinct values.
+impl Rand {
+    pub fn gaussian(&self, mean: f32, standard_deviation: f32) -> f32 {
+        (mean as f64 + self.standard_gaussian_f64() * standard_deviation as f64) as f32
+    }
+
+    // Time between events that happen rate times per unit of time, on average.
+    pub fn exponential(&self, rate: f32) -> f32 {
+        (-self.zero_to_one_exclusive().ln() / rate as f64) as f32
+    }
+
+    // How many events happen in a unit of time, when mean happen on average.
+    pub fn poisson(&self, mean: f32) -> usize {
+        let mean = mean as f64;
+
+        if mean <= 0.0 {
+            0
+        } else if mean < 30.0 {
+            self.poisson_by_multiplication(mean)
+        } else {
+            self.poisson_by_transformed_rejection(mean)
+        }
+    }
+
+    // The mean is shape * scale.
+    pub fn gamma(&self, shape: f32, scale: f32) -> f32 {
+        (self.gamma_f64(shape as f64) * scale as f64) as f32
+    }
+
+    // Between 0 and 1. The mean is alpha / (alpha + beta), and bigger values of
+    // both make it more peaked.
+    pub fn beta(&self, alpha: f32, beta: f32) -> NormalizedF32 {
+        let x = self.gamma_f64(alpha as f64);
+        let y = self.gamma_f64(beta as f64);
+
+        (x / (x + y)) as f32
+    }
+
+    // Its logarithm has a gaussian distribution with this mean and standard
+    // deviation. Always positive, with a long tail of big values.
+    pub fn log_normal(&self, mean: f32, standard_deviation: f32) -> f32 {
+        self.gaussian(mean, standard_deviation).exp()
+    }
+
+    // The gaussian distribution wrapped around a circle. Higher concentrations
+    // stay closer to the mean. Returns turns from 0 to 1.
+    pub fn von_mises(&self, mean: NumberOfTurns, concentration: f32) -> NumberOfTurns {
+        let concentration = concentration as f64;
+        if concentration < 1e-6 {
+            return self.zero_to_one_f64() as f32;
+        }
+
+        // Best and Fisher's algorithm.
+        let tau = 1.0 + (1.0 + 4.0 * concentration * concentration).sqrt();
+        let rho = (tau - (2.0 * tau).sqrt()) / (2.0 * concentration);
+        let r = (1.0 + rho * rho) / (2.0 * rho);
+
+        loop {
+            let z = (PI * self.zero_to_one_f64()).cos();
+            let f = (1.0 + r * z) / (r + z);
+            let c = concentration * (r - f);
+            let u = self.zero_to_one_exclusive();
+
+            if c * (2.0 - c) - u > 0.0 || (c / u).ln() + 1.0 - c >= 0.0 {
+                let radians = f.clamp(-1.0, 1.0).acos();
+                let signed_radians = if self.zero_to_one_f64() < 0.5 {
+                    -radians
+                } else {
+                    radians
+                };
+
+                return (mean as f64 + signed_radians / TAU).rem_euclid(1.0) as f32;
+            }
+        }
+    }
+
+    // Never less than scale. Smaller shapes give longer tails, so a few values are
+    // much bigger than the rest.
+    pub fn pareto(&self, scale: f32, shape: f32) -> f32 {
+        (scale as f64 * self.zero_to_one_exclusive().powf(-1.0 / shape as f64)) as f32
+    }
+
+    // Takes constant time, so it's much faster than weighted_choice for big tables
+    // that get sampled many times.
+    pub fn alias_choice<'a, Choice>(&self, table: &'a AliasTable<Choice>) -> &'a Choice {
+        let column = self.index(&table.choices);
+
+        if self.zero_to_one_f64() < table.probabilities[column] {
+            &table.choices[column]
+        } else {
+            &table.choices[table.aliases[column]]
+        }
+    }
+
+    // Fisher-Yates.
+    pub fn shuffle<T>(&self, elements: &mut [T]) {
+        for index in (1..elements.len()).rev() {
+            let other_index = self.range(0..=index);
+            elements.swap(index, other_index);
+        }
+    }
+
+    // Picks count different elements, in random order.
+    pub fn sample_without_replacement<'v, T>(&self, elements: &'v [T], count: usize) -> Vec<&'v T> {
+        self.sample_indices(elements.len(), count)
+            .into_iter()
+            .map(|index| &elements[index])
+            .collect()
+    }
+
+    // count different indices below num_indices, in random order.
+    pub fn sample_indices(&self, num_indices: usize, count: usize) -> Vec<usize> {
+        if count > num_indices {
+            panic!("Can't pick {count} different indices out of {num_indices}.");
+        }
+
+        // Only shuffle as far as we need to.
+        let mut indices: Vec<usize> = (0..num_indices).collect();
+        for index in 0..count {
+            let other_index = self.range(index..num_indices);
+            indices.swap(index, other_index);
+        }
+
+        indices.truncate(count);
+        indices
+    }
+
+    // 0 exclusive to 1 inclusive, so it's safe to take the logarithm of.
+    fn zero_to_one_exclusive(&self) -> f64 {
+        1.0 - self.zero_to_one_f64()
+    }
+
+    // Marsaglia's polar method.
+    fn standard_gaussian_f64(&self) -> f64 {
+        loop {
+            let x = self.zero_to_one_f64() * 2.0 - 1.0;
+            let y = self.zero_to_one_f64() * 2.0 - 1.0;
+            let squared_length = x * x + y * y;
+
+            if 0.0 < squared_length && squared_length < 1.0 {
+                return x * (-2.0 * squared_length.ln() / squared_length).sqrt();
+            }
+        }
+    }
+
+    // Marsaglia and Tsang's method, with a scale of 1.
+    fn gamma_f64(&self, shape: f64) -> f64 {
+        if shape < 1.0 {
+            let boost = self.zero_to_one_exclusive().powf(1.0 / shape);
+            return self.gamma_f64(shape + 1.0) * boost;
+        }
+
+        let d = shape - 1.0 / 3.0;
+        let c = 1.0 / (9.0 * d).sqrt();
+
+        loop {
+            let x = self.standard_gaussian_f64();
+            let v = (1.0 + c * x).powi(3);
+            if v <= 0.0 {
+                continue;
+            }
+
+            let u = self.zero_to_one_exclusive();
+            if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+                return d * v;
+            }
+        }
+    }
+
+    // Knuth's method. It takes about mean steps, so it's only for small means.
+    fn poisson_by_multiplication(&self, mean: f64) -> usize {
+        let limit = (-mean).exp();
+        let mut product = self.zero_to_one_exclusive();
+        let mut count = 0;
+
+        while product > limit {
+            product *= self.zero_to_one_exclusive();
+            count += 1;
+        }
+
+        count
+    }
+
+    // Hörmann's PTRS, which takes about the same time whatever the mean.
+    fn poisson_by_transformed_rejection(&self, mean: f64) -> usize {
+        let sqrt_mean = mean.sqrt();
+        let b = 0.931 + 2.53 * sqrt_mean;
+        let a = -0.059 + 0.02483 * b;
+        let inverse_alpha = 1.1239 + 1.1328 / (b - 3.4);
+        let v_r = 0.9277 - 3.6224 / (b - 2.0);
+
+        loop {
+            let u = self.zero_to_one_f64() - 0.5;
+            let v = self.zero_to_one_exclusive();
+            let us = 0.5 - u.abs();
+            let k = ((2.0 * a / us + b) * u + mean + 0.43).floor();
+
+            if us >= 0.07 && v <= v_r {
+                return k as usize;
+            }
+
+            if k < 0.0 || (us < 0.013 && v > us) {
+                continue;
+            }
+
+            let log_acceptance = v.ln() + inverse_alpha.ln() - (a / (us * us) + b).ln();
+            if log_acceptance <= -mean + k * mean.ln() - ln_gamma(k + 1.0) {
+                return k as usize;
+            }
+        }
+    }
+}
+
+// Vose's alias method. Building the table takes linear time, but then every
+// sample takes constant time.
+pub struct AliasTable<Choice> {
+    choices: Vec<Choice>,
+    probabilities: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl<Choice> AliasTable<Choice> {
+    pub fn new(choices_with_weights: Vec<(f32, Choice)>) -> AliasTable<Choice> {
+        if choices_with_weights.is_empty() {
+            panic!("Can't make an alias table without any choices.");
+        }
+
+        let has_invalid_weight = choices_with_weights
+            .iter()
+            .any(|(weight, _choice)| !weight.is_finite() || *weight < 0.0);
+        if has_invalid_weight {
+            panic!("Can't make an alias table with negative, infinite or NaN weights.");
+        }
+
+        let num_choices = choices_with_weights.len();
+        let weight_total: f64 = choices_with_weights
+            .iter()
+            .map(|(weight, _choice)| *weight as f64)
+            .sum();
+
+        if weight_total <= 0.0 {
+            panic!("Can't make an alias table whose weights are all zero.");
+        }
+
+        // Scaled so the average is 1.
+        let mut probabilities: Vec<f64> = choices_with_weights
+            .iter()
+            .map(|(weight, _choice)| *weight as f64 * num_choices as f64 / weight_total)
+            .collect();
+        let mut aliases: Vec<usize> = (0..num_choices).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..num_choices).partition(|index| probabilities[*index] < 1.0);
+
+        // Each small column gets topped up to 1 by a large one.
+        while let (Some(small_index), Some(large_index)) = (small.pop(), large.pop()) {
+            aliases[small_index] = large_index;
+            probabilities[large_index] -= 1.0 - probabilities[small_index];
+
+            if probabilities[large_index] < 1.0 {
+                small.push(large_index);
+            } else {
+                large.push(large_index);
+            }
+        }
+
+        // Whatever's left is 1, give or take rounding errors.
+        for index in small.into_iter().chain(large) {
+            probabilities[index] = 1.0;
+        }
+
+        AliasTable {
+            choices: choices_with_weights
+                .into_iter()
+                .map(|(_weight, choice)| choice)
+                .collect(),
+            probabilities,
+            aliases,
+        }
+    }
+}
+
+// Lanczos' approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const coefficients: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // The reflection formula.
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = coefficients[1..]
+        .iter()
+        .enumerate()
+        .fold(coefficients[0], |sum, (index, coefficient)| {
+            sum + coefficient / (x + index as f64 + 1.0)
+        });
+
+    0.5 * TAU.ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::f64::consts::TAU;
+
+    const num_samples: usize = 50_000;
+
+    // The mean has to be within 5 standard errors, and the variance within 10%.
+    fn assert_mean_and_variance(samples: Vec<f64>, mean: f64, variance: f64) {
+        let count = samples.len() as f64;
+        let sample_mean = samples.iter().sum::<f64>() / count;
+        let sample_variance = samples
+            .iter()
+            .map(|sample| (sample - sample_mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0);
+
+        let standard_error = (variance / count).sqrt();
+        assert!(
+            (sample_mean - mean).abs() < 5.0 * standard_error,
+            "Expected a mean of {mean}, got {sample_mean}."
+        );
+        assert!(
+            (sample_variance - variance).abs() < 0.1 * variance,
+            "Expected a variance of {variance}, got {sample_variance}."
+        );
+    }
+
+    fn samples(sample: impl Fn(&Rand) -> f64) -> Vec<f64> {
+        let rand = Rand::from_seed(7);
+        (0..num_samples).map(|_| sample(&rand)).collect()
+    }
+
+    #[test]
+    fn zero_to_one_f64_is_uniform_and_precise() {
+        let values = samples(|rand| rand.zero_to_one_f64());
+
+        let mut distinct_values = values.clone();
+        distinct_values.sort_by(|a, b| a.total_cmp(b));
+        distinct_values.dedup();
+        // With only 65,536 possible values, about a third would be repeats.
+        assert!(distinct_values.len() > num_samples - 10);
+
+        assert_mean_and_variance(values, 0.5, 1.0 / 12.0);
+    }
+
+    #[test]
+    fn gaussian() {
+        let values = samples(|rand| rand.gaussian(3.0, 2.0) as f64);
+        assert_mean_and_variance(values, 3.0, 4.0);
+    }
+
+    #[test]
+    fn exponential() {
+        let values = samples(|rand| rand.exponential(2.0) as f64);
+        assert_mean_and_variance(values, 0.5, 0.25);
+    }
+
+    #[test]
+    fn poisson() {
+        for mean in [4.0, 100.0] {
+            let values = samples(|rand| rand.poisson(mean as f32) as f64);
+            assert_mean_and_variance(values, mean, mean);
+        }
+    }
+
+    #[test]
+    fn gamma() {
+        for (shape, scale) in [(0.5, 2.0), (3.0, 1.5)] {
+            let values = samples(|rand| rand.gamma(shape as f32, scale as f32) as f64);
+            assert_mean_and_variance(values, shape * scale, shape * scale * scale);
+        }
+    }
+
+    #[test]
+    fn beta() {
+        let (alpha, beta) = (2.0, 5.0);
+        let values = samples(|rand| rand.beta(alpha as f32, beta as f32) as f64);
+
+        let total = alpha + beta;
+        let variance = alpha * beta / (total * total * (total + 1.0));
+        assert_mean_and_variance(values, alpha / total, variance);
+    }
+
+    #[test]
+    fn log_normal() {
+        let (mean, standard_deviation): (f64, f64) = (0.0, 0.5);
+        let values = samples(|rand| rand.log_normal(mean as f32, standard_deviation as f32) as f64);
+
+        let variance = standard_deviation.powi(2);
+        let expected_mean = (mean + variance / 2.0).exp();
+        let expected_variance = (variance.exp() - 1.0) * (2.0 * mean + variance).exp();
+        assert_mean_and_variance(values, expected_mean, expected_variance);
+    }
+
+    #[test]
+    fn von_mises() {
+        let mean = 0.9;
+        let concentration = 4.0;
+        let angles = samples(|rand| rand.von_mises(mean as f32, concentration as f32) as f64);
+
+        assert!(angles.iter().all(|angle| (0.0..1.0).contains(angle)));
+
+        // The average of cos(angle - mean) is I₁(κ) / I₀(κ).
+        let bessel = |order: i32, x: f64| {
+            (0..30)
+                .map(|k| {
+                    let factorial = |n: i32| (1..=n).map(|i| i as f64).product::<f64>();
+                    (x / 2.0).powi(2 * k + order) / (factorial(k) * factorial(k + order))
+                })
+                .sum::<f64>()
+        };
+        let expected_cos = bessel(1, concentration) / bessel(0, concentration);
+
+        let count = angles.len() as f64;
+        let average_cos = angles
+            .iter()
+            .map(|angle| ((angle - mean) * TAU).cos())
+            .sum::<f64>()
+            / count;
+        let average_sin = angles
+            .iter()
+            .map(|angle| ((angle - mean) * TAU).sin())
+            .sum::<f64>()
+            / count;
+
+        assert!((average_cos - expected_cos).abs() < 0.01);
+        assert!(average_sin.abs() < 0.01);
+    }
+
+    #[test]
+    fn pareto() {
+        let (scale, shape) = (1.0, 5.0);
+        let values = samples(|rand| rand.pareto(scale as f32, shape as f32) as f64);
+
+        assert!(values.iter().all(|value| *value >= scale));
+
+        // The variance of the sample variance is too high to check it reliably.
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!((mean - shape * scale / (shape - 1.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn alias_choice_follows_the_weights() {
+        let rand = Rand::from_seed(7);
+        let weights = [1.0, 2.0, 3.0, 4.0, 0.0];
+        let table = AliasTable::new(weights.iter().copied().zip(0_usize..).collect());
+
+        let mut counts = [0; 5];
+        for _ in 0..num_samples {
+            counts[*rand.alias_choice(&table)] += 1;
+        }
+
+        for (count, weight) in counts.iter().zip(weights) {
+            let frequency = *count as f64 / num_samples as f64;
+            assert!((frequency - weight as f64 / 10.0).abs() < 0.01);
+        }
+        assert_eq!(counts[4], 0);
+    }
+
+    #[test]
+    fn alias_table_rejects_weights_it_cant_sample() {
+        let invalid_weights = [
+            vec![1.0, -1.0],
+            vec![1.0, f32::NAN],
+            vec![1.0, f32::INFINITY],
+            vec![0.0, 0.0],
+        ];
+
+        for weights in invalid_weights {
+            let result = std::panic::catch_unwind(|| {
+                AliasTable::new(weights.into_iter().zip(0_usize..).collect())
+            });
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn shuffling_and_sampling_keep_every_element_once() {
+        let rand = Rand::from_seed(7);
+        let elements: Vec<usize> = (0..100).collect();
+
+        let mut shuffled = elements.clone();
+        rand.shuffle(&mut shuffled);
+        assert_ne!(shuffled, elements);
+        shuffled.sort();
+        assert_eq!(shuffled, elements);
+
+        let mut sample: Vec<usize> = rand
+            .sample_without_replacement(&elements, 30)
+            .into_iter()
+            .copied()
+            .collect();
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 30);
+    }
+}
@@ -1,6 +1,7 @@
 use nannou::prelude::*;
 
 use super::rect::RectExtension;
+use crate::window_dimensions::artwork_window;
 
 pub trait AppExtension {
     fn normalized_mouse_x(&self) -> f32;
@@ -11,11 +12,11 @@ pub trait AppExtension {
 impl AppExtension for App {
     fn normalized_mouse_x(&self) -> f32 {
         let mouse_x = self.mouse.x;
-        self.main_window().rect().normalize_w(mouse_x)
+        artwork_window(self).rect().normalize_w(mouse_x)
     }
     fn normalized_mouse_y(&self) -> f32 {
         let mouse_y = self.mouse.y;
-        self.main_window().rect().normalize_h(mouse_y)
+        artwork_window(self).rect().normalize_h(mouse_y)
     }
     fn normalized_mouse_xy(&self) -> Point2 {
         let mouse_x = self.normalized_mouse_x();
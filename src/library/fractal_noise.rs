@@ -0,0 +1,548 @@
+use super::rand_streams::{hash_name, mix};
+use crate::prelude::*;
+use crate::snapshot::rand::Rand;
+use nannou::noise::NoiseFn;
+use std::f64::consts::SQRT_2;
+
+// Fractal noise sums several octaves of noise, each at a higher frequency and a
+// lower amplitude than the last, so we get detail at many scales. Everything here
+// is seeded from the Rand's seed and returns values from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalOptions {
+    pub num_octaves: usize,
+    // The frequency of the first octave.
+    pub frequency: f32,
+    // Each octave's frequency is the previous one's times this.
+    pub lacunarity: f32,
+    // Each octave's amplitude is the previous one's times this.
+    pub gain: f32,
+    // When set, the noise repeats every tile_size units along every axis, for
+    // textures that need to wrap. Frequencies get rounded so that a whole number of
+    // noise cells fits in a tile.
+    pub tile_size: Option<f32>,
+}
+
+impl Default for FractalOptions {
+    fn default() -> Self {
+        FractalOptions {
+            num_octaves: 4,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            gain: 0.5,
+            tile_size: None,
+        }
+    }
+}
+
+// Worley (cellular) noise measures how far a point is from randomly scattered
+// feature points, one per cell. Distances are divided by a cell's diagonal and
+// clamped, so they go from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorleyDistances {
+    // To the nearest feature point. Looks like a field of bubbles.
+    pub f1: NormalizedF32,
+    // To the second nearest feature point.
+    pub f2: NormalizedF32,
+}
+
+impl WorleyDistances {
+    // Zero along the borders between cells, so it looks like cracked mud or veins.
+    pub fn f2_minus_f1(&self) -> NormalizedF32 {
+        self.f2 - self.f1
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Shape {
+    Fbm,
+    // Sharp creases where the noise crosses zero, like mountain ridges.
+    Ridged,
+    // Soft bumps, like clouds.
+    Billow,
+}
+
+impl Rand {
+    // Fractal Brownian motion.
+    pub fn fbm_xy(&self, point: &Point2, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Fbm, options, |octave| self.octave_xy(point, octave))
+    }
+
+    pub fn fbm_xyz(&self, point: &Point3, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Fbm, options, |octave| self.octave_xyz(point, octave))
+    }
+
+    pub fn ridged_xy(&self, point: &Point2, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Ridged, options, |octave| {
+            self.octave_xy(point, octave)
+        })
+    }
+
+    pub fn ridged_xyz(&self, point: &Point3, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Ridged, options, |octave| {
+            self.octave_xyz(point, octave)
+        })
+    }
+
+    pub fn billow_xy(&self, point: &Point2, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Billow, options, |octave| {
+            self.octave_xy(point, octave)
+        })
+    }
+
+    pub fn billow_xyz(&self, point: &Point3, options: &FractalOptions) -> NormalizedF32 {
+        fractal(Shape::Billow, options, |octave| {
+            self.octave_xyz(point, octave)
+        })
+    }
+
+    // Pushes the point around by fbm noise, up to strength units along each axis.
+    // Sampling other noise at the warped point gives swirly, marbled shapes.
+    pub fn domain_warp_xy(
+        &self,
+        point: &Point2,
+        strength: f32,
+        options: &FractalOptions,
+    ) -> Point2 {
+        // Offsetting the point gives each axis an unrelated noise field.
+        let x = self.fbm_xy(&(*point + pt2(warp_offset, 0.0)), options);
+        let y = self.fbm_xy(&(*point + pt2(0.0, warp_offset)), options);
+
+        *point + pt2(x, y).denormalize_to_range(-strength, strength)
+    }
+
+    pub fn domain_warp_xyz(
+        &self,
+        point: &Point3,
+        strength: f32,
+        options: &FractalOptions,
+    ) -> Point3 {
+        let x = self.fbm_xyz(&(*point + pt3(warp_offset, 0.0, 0.0)), options);
+        let y = self.fbm_xyz(&(*point + pt3(0.0, warp_offset, 0.0)), options);
+        let z = self.fbm_xyz(&(*point + pt3(0.0, 0.0, warp_offset)), options);
+
+        let displacement = pt3(x, y, z) * 2.0 - Vec3::ONE;
+        *point + displacement * strength
+    }
+
+    pub fn warped_fbm_xy(
+        &self,
+        point: &Point2,
+        strength: f32,
+        options: &FractalOptions,
+    ) -> NormalizedF32 {
+        self.fbm_xy(&self.domain_warp_xy(point, strength, options), options)
+    }
+
+    pub fn warped_fbm_xyz(
+        &self,
+        point: &Point3,
+        strength: f32,
+        options: &FractalOptions,
+    ) -> NormalizedF32 {
+        self.fbm_xyz(&self.domain_warp_xyz(point, strength, options), options)
+    }
+
+    pub fn worley_xy(&self, point: &Point2, frequency: f32) -> WorleyDistances {
+        let octave = Octave::new(0, frequency as f64, None);
+        self.worley_2d(point, &octave)
+    }
+
+    pub fn worley_xyz(&self, point: &Point3, frequency: f32) -> WorleyDistances {
+        let octave = Octave::new(0, frequency as f64, None);
+        self.worley_3d(point, &octave)
+    }
+
+    // Repeats every tile_size units along each axis.
+    pub fn tileable_worley_xy(
+        &self,
+        point: &Point2,
+        frequency: f32,
+        tile_size: f32,
+    ) -> WorleyDistances {
+        let octave = Octave::new(0, frequency as f64, Some(tile_size));
+        self.worley_2d(point, &octave)
+    }
+
+    pub fn tileable_worley_xyz(
+        &self,
+        point: &Point3,
+        frequency: f32,
+        tile_size: f32,
+    ) -> WorleyDistances {
+        let octave = Octave::new(0, frequency as f64, Some(tile_size));
+        self.worley_3d(point, &octave)
+    }
+
+    // From -1 to 1.
+    fn octave_xy(&self, point: &Point2, octave: &Octave) -> f64 {
+        let x = point.x as f64 * octave.frequency;
+        let y = point.y as f64 * octave.frequency;
+
+        match octave.period {
+            // Shift each octave so they don't all line up around the origin.
+            None => {
+                let offset = octave.index as f64 * octave_offset;
+                self.super_simplex_noise_generator()
+                    .get([x + offset, y + offset])
+            }
+            Some(period) => {
+                let key = self.noise_key("tileable gradient noise", octave.index);
+                periodic_gradient_noise_2d(key, [x, y], period)
+            }
+        }
+    }
+
+    fn octave_xyz(&self, point: &Point3, octave: &Octave) -> f64 {
+        let x = point.x as f64 * octave.frequency;
+        let y = point.y as f64 * octave.frequency;
+        let z = point.z as f64 * octave.frequency;
+
+        match octave.period {
+            None => {
+                let offset = octave.index as f64 * octave_offset;
+                self.super_simplex_noise_generator()
+                    .get([x + offset, y + offset, z + offset])
+            }
+            Some(period) => {
+                let key = self.noise_key("tileable gradient noise", octave.index);
+                periodic_gradient_noise_3d(key, [x, y, z], period)
+            }
+        }
+    }
+
+    fn worley_2d(&self, point: &Point2, octave: &Octave) -> WorleyDistances {
+        let key = self.noise_key("worley", 0);
+        let point = [
+            point.x as f64 * octave.frequency,
+            point.y as f64 * octave.frequency,
+        ];
+        let cell = [point[0].floor() as i64, point[1].floor() as i64];
+
+        let mut nearest = NearestTwo::new();
+        for dx in -worley_reach..=worley_reach {
+            for dy in -worley_reach..=worley_reach {
+                let neighbour = [cell[0] + dx, cell[1] + dy];
+                let hash = hash_lattice(key, &wrap(&neighbour, octave.period));
+                let feature_point = [
+                    neighbour[0] as f64 + unit_f64(hash),
+                    neighbour[1] as f64 + unit_f64(mix(hash)),
+                ];
+
+                nearest.add(distance(&point, &feature_point));
+            }
+        }
+
+        nearest.to_worley_distances(SQRT_2)
+    }
+
+    fn worley_3d(&self, point: &Point3, octave: &Octave) -> WorleyDistances {
+        let key = self.noise_key("worley", 0);
+        let point = [
+            point.x as f64 * octave.frequency,
+            point.y as f64 * octave.frequency,
+            point.z as f64 * octave.frequency,
+        ];
+        let cell = [
+            point[0].floor() as i64,
+            point[1].floor() as i64,
+            point[2].floor() as i64,
+        ];
+
+        let mut nearest = NearestTwo::new();
+        for dx in -worley_reach..=worley_reach {
+            for dy in -worley_reach..=worley_reach {
+                for dz in -worley_reach..=worley_reach {
+                    let neighbour = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let hash = hash_lattice(key, &wrap(&neighbour, octave.period));
+                    let feature_point = [
+                        neighbour[0] as f64 + unit_f64(hash),
+                        neighbour[1] as f64 + unit_f64(mix(hash)),
+                        neighbour[2] as f64 + unit_f64(mix(mix(hash))),
+                    ];
+
+                    nearest.add(distance(&point, &feature_point));
+                }
+            }
+        }
+
+        nearest.to_worley_distances(3.0_f64.sqrt())
+    }
+
+    // Each kind of noise, and each octave, gets its own lattice of random values.
+    fn noise_key(&self, name: &str, octave_index: usize) -> u64 {
+        mix(mix(self.seed() ^ hash_name(name)) ^ octave_index as u64)
+    }
+}
+
+// How many cells out from the point's own cell we look for feature points. The
+// nearest one is always in the next cell over, but the second nearest can be two
+// cells away.
+const worley_reach: i64 = 2;
+
+// Far enough apart that octaves and warp axes sample unrelated parts of the noise.
+const octave_offset: f64 = 1000.0;
+const warp_offset: f32 = 5000.0;
+
+struct Octave {
+    index: usize,
+    frequency: f64,
+    // In noise cells. None if the noise doesn't tile.
+    period: Option<i64>,
+}
+
+impl Octave {
+    fn new(index: usize, frequency: f64, tile_size: Option<f32>) -> Octave {
+        match tile_size {
+            None => Octave {
+                index,
+                frequency,
+                period: None,
+            },
+            Some(tile_size) => {
+                let tile_size = tile_size as f64;
+                let period = (frequency * tile_size).round().max(1.0);
+
+                Octave {
+                    index,
+                    frequency: period / tile_size,
+                    period: Some(period as i64),
+                }
+            }
+        }
+    }
+}
+
+// The octaves are normalized by the total amplitude, so the result always stays
+// between 0 and 1 whatever the number of octaves or gain.
+fn fractal<Noise>(shape: Shape, options: &FractalOptions, noise: Noise) -> NormalizedF32
+where
+    Noise: Fn(&Octave) -> f64,
+{
+    let mut total = 0.0;
+    let mut total_amplitude = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = options.frequency as f64;
+    // Ridged multifractal noise makes the higher octaves fade out in the valleys,
+    // so the ridges stay crisp.
+    let mut ridge_weight = 1.0;
+
+    for index in 0..options.num_octaves.max(1) {
+        let octave = Octave::new(index, frequency, options.tile_size);
+        let value = noise(&octave).clamp(-1.0, 1.0);
+
+        let contribution = match shape {
+            Shape::Fbm => (value + 1.0) / 2.0,
+            Shape::Billow => value.abs(),
+            Shape::Ridged => {
+                let ridge = (1.0 - value.abs()).powi(2) * ridge_weight;
+                ridge_weight = (ridge * 2.0).clamp(0.0, 1.0);
+                ridge
+            }
+        };
+
+        total += contribution * amplitude;
+        total_amplitude += amplitude;
+
+        amplitude *= options.gain as f64;
+        frequency *= options.lacunarity as f64;
+    }
+
+    (total / total_amplitude) as f32
+}
+
+// Perlin's gradient noise, with the lattice wrapped every period cells. From -1 to
+// 1.
+fn periodic_gradient_noise_2d(key: u64, point: [f64; 2], period: i64) -> f64 {
+    let cell = [point[0].floor(), point[1].floor()];
+    let offset = [point[0] - cell[0], point[1] - cell[1]];
+    let cell = [cell[0] as i64, cell[1] as i64];
+
+    let corner = |dx: i64, dy: i64| {
+        let lattice_point = wrap(&[cell[0] + dx, cell[1] + dy], Some(period));
+        let angle = unit_f64(hash_lattice(key, &lattice_point)) * std::f64::consts::TAU;
+
+        angle.cos() * (offset[0] - dx as f64) + angle.sin() * (offset[1] - dy as f64)
+    };
+
+    let u = fade(offset[0]);
+    let v = fade(offset[1]);
+
+    let bottom = lerp(corner(0, 0), corner(1, 0), u);
+    let top = lerp(corner(0, 1), corner(1, 1), u);
+
+    // With unit gradients, 2D gradient noise only reaches about ±0.7.
+    lerp(bottom, top, v) * SQRT_2
+}
+
+fn periodic_gradient_noise_3d(key: u64, point: [f64; 3], period: i64) -> f64 {
+    let cell = [point[0].floor(), point[1].floor(), point[2].floor()];
+    let offset = [point[0] - cell[0], point[1] - cell[1], point[2] - cell[2]];
+    let cell = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+
+    // The edges of a cube, as in Perlin's improved noise.
+    const gradients: [[f64; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        let lattice_point = wrap(&[cell[0] + dx, cell[1] + dy, cell[2] + dz], Some(period));
+        let gradient = gradients[(hash_lattice(key, &lattice_point) % 12) as usize];
+
+        gradient[0] * (offset[0] - dx as f64)
+            + gradient[1] * (offset[1] - dy as f64)
+            + gradient[2] * (offset[2] - dz as f64)
+    };
+
+    let u = fade(offset[0]);
+    let v = fade(offset[1]);
+    let w = fade(offset[2]);
+
+    let near = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+        v,
+    );
+    let far = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+        v,
+    );
+
+    lerp(near, far, w)
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(from: f64, to: f64, amount: f64) -> f64 {
+    from + (to - from) * amount
+}
+
+fn wrap<const N: usize>(cell: &[i64; N], period: Option<i64>) -> [i64; N] {
+    match period {
+        Some(period) => cell.map(|coordinate| coordinate.rem_euclid(period)),
+        None => *cell,
+    }
+}
+
+pub(super) fn hash_lattice(key: u64, lattice_point: &[i64]) -> u64 {
+    lattice_point
+        .iter()
+        .fold(key, |hash, coordinate| mix(hash ^ *coordinate as u64))
+}
+
+// 0 inclusive to 1 exclusive.
+pub(super) fn unit_f64(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn distance<const N: usize>(from: &[f64; N], to: &[f64; N]) -> f64 {
+    from.iter()
+        .zip(to)
+        .map(|(from, to)| (to - from).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+struct NearestTwo {
+    distances: [f64; 2],
+}
+
+impl NearestTwo {
+    fn new() -> NearestTwo {
+        NearestTwo {
+            distances: [f64::MAX; 2],
+        }
+    }
+
+    fn add(&mut self, distance: f64) {
+        if distance < self.distances[0] {
+            self.distances = [distance, self.distances[0]];
+        } else if distance < self.distances[1] {
+            self.distances[1] = distance;
+        }
+    }
+
+    fn to_worley_distances(&self, cell_diagonal: f64) -> WorleyDistances {
+        let normalize = |distance: f64| (distance / cell_diagonal).min(1.0) as f32;
+
+        WorleyDistances {
+            f1: normalize(self.distances[0]),
+            f2: normalize(self.distances[1]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn fractal_noise_stays_between_zero_and_one() {
+        let rand = Rand::from_seed(7);
+        let options = FractalOptions {
+            num_octaves: 6,
+            frequency: 3.0,
+            ..FractalOptions::default()
+        };
+
+        for index in 0..500 {
+            let point = rand.xy().times(10.0);
+            let point_3d = rand.xyz().times(10.0);
+
+            let values = [
+                rand.fbm_xy(&point, &options),
+                rand.ridged_xy(&point, &options),
+                rand.billow_xy(&point, &options),
+                rand.warped_fbm_xyz(&point_3d, 0.5, &options),
+                rand.worley_xy(&point, 2.0).f2_minus_f1(),
+                rand.worley_xyz(&point_3d, 2.0).f1,
+            ];
+
+            for value in values {
+                assert!((0.0..=1.0).contains(&value), "{value} at index {index}");
+            }
+        }
+    }
+
+    #[test]
+    fn tileable_noise_wraps() {
+        let rand = Rand::from_seed(7);
+        let options = FractalOptions {
+            tile_size: Some(2.0),
+            frequency: 2.7,
+            lacunarity: 1.9,
+            ..FractalOptions::default()
+        };
+
+        let point = pt2(0.3, 1.7);
+        let wrapped = point + pt2(2.0, -2.0);
+
+        assert!((rand.fbm_xy(&point, &options) - rand.fbm_xy(&wrapped, &options)).abs() < 0.0001);
+        assert!(
+            (rand.tileable_worley_xy(&point, 3.0, 2.0).f1
+                - rand.tileable_worley_xy(&wrapped, 3.0, 2.0).f1)
+                .abs()
+                < 0.0001
+        );
+
+        let point_3d = pt3(0.3, 1.7, 0.9);
+        let wrapped_3d = point_3d + pt3(0.0, 2.0, 4.0);
+        assert!(
+            (rand.ridged_xyz(&point_3d, &options) - rand.ridged_xyz(&wrapped_3d, &options)).abs()
+                < 0.0001
+        );
+    }
+}
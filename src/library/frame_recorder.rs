@@ -0,0 +1,59 @@
+use crate::library::command_line;
+use crate::snapshot::manifest;
+use std::fs;
+use std::path::PathBuf;
+
+// Saves a numbered frame after every batch of draws (or every Nth batch), so we
+// can turn the way a piece builds up into a video.
+//
+// While recording, every batch has the same number of draws (see DrawScheduling)
+// instead of however many fit in a 60th of a second. That way frame 10 always
+// shows the same thing, no matter how fast the machine is.
+pub struct FrameRecorder {
+    folder: PathBuf,
+    every_nth_batch: usize,
+    num_batches: usize,
+    num_frames: usize,
+}
+
+impl FrameRecorder {
+    // --record-frames [--every 4] [--draws-per-frame 10]
+    pub fn from_command_line(snapshot_name: &str) -> Option<FrameRecorder> {
+        if !command_line::has_flag("--record-frames") {
+            return None;
+        }
+
+        let every_nth_batch = command_line::value_of("--every").map(|value| {
+            value
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("--every expects a whole number, got {value}."))
+        });
+
+        let folder = manifest::folder().join("frames").join(snapshot_name);
+        fs::create_dir_all(&folder).expect("Couldn't create the frames folder.");
+
+        Some(FrameRecorder {
+            folder,
+            every_nth_batch: every_nth_batch.unwrap_or(1).max(1),
+            num_batches: 0,
+            num_frames: 0,
+        })
+    }
+
+    // Call after every batch. Returns where to save the frame if this batch should
+    // be recorded. The last batch is always recorded, so the sequence ends on the
+    // finished piece.
+    pub fn next_frame_path(&mut self, is_last_batch: bool) -> Option<PathBuf> {
+        let should_record = self.num_batches % self.every_nth_batch == 0 || is_last_batch;
+        self.num_batches += 1;
+
+        if !should_record {
+            return None;
+        }
+
+        let path = self.folder.join(format!("{:05}.png", self.num_frames));
+        self.num_frames += 1;
+
+        Some(path)
+    }
+}
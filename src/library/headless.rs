@@ -0,0 +1,37 @@
+use crate::prelude::*;
+
+// Renders the artwork to completion without opening a window, so we can render
+// on machines that don't have a display or a GPU. The image ends up in the images
+// folder, just like when we capture a frame from the window.
+pub fn render() {
+    render_model(Model::headless());
+}
+
+// Renders one variation of the current artwork per seed. Each one gets its own
+// snapshot, tagged with the name of the sweep so we can find them all later.
+pub fn render_seeds(seeds: Vec<u64>) {
+    let seed_sweep = format!("sweep_{}", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
+    let num_seeds = seeds.len();
+
+    for (index, seed) in seeds.into_iter().enumerate() {
+        println!("Rendering seed {seed} ({} of {num_seeds}).", index + 1);
+
+        let mut model = Model::headless_with_seed(seed);
+        model.snapshot.metadata.record_seed_sweep(&seed_sweep);
+
+        render_model(model);
+    }
+
+    println!("Finished {seed_sweep}. See them all with --contact-sheet --sweep {seed_sweep}.");
+}
+
+fn render_model(mut model: Model) {
+    let mut rasterizer = Rasterizer::new(&model.container);
+
+    while !model.done_rendering {
+        model.draw_headlessly(&mut rasterizer);
+        model.update_headlessly(&rasterizer);
+    }
+
+    snapshot::exit_headlessly(&model.snapshot);
+}
@@ -0,0 +1,297 @@
+use crate::prelude::*;
+use crate::snapshot::manifest;
+use duct::{cmd, Handle};
+use libloading::{Library, Symbol};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// The example target in Cargo.toml that builds src/artwork/ as a dynamic library.
+const library_name: &str = "hot_reload_artwork";
+const check_interval: Duration = Duration::from_millis(500);
+
+type CreateArtwork = fn(CreateArtworkParams) -> Box<dyn Artwork>;
+type ShareGlobals = fn(SharedGlobals);
+type BuildHash = extern "C" fn() -> u64;
+
+// With --hot-reload, we rebuild the artwork whenever something in src/artwork/
+// changes and start the render over with the same seed, without restarting the app.
+//
+// Only the artwork gets reloaded. The rest of src/ is shared with the app, so the
+// types passed back and forth have to stay exactly the same, and changes there
+// still need a restart. We watch it and Cargo.toml too, and stop reloading once
+// they change. Since we can't catch everything that way, like an edit to the
+// Artwork trait in src/artwork/mod.rs, build.rs also hashes the shared code into
+// both builds, and a library whose hash doesn't match the app's never gets called.
+//
+// The library also gets its own copy of any statics, so the ones the artwork reads
+// get handed over in SharedGlobals after each load.
+pub struct HotReloader {
+    library: Library,
+    // Each build gets copied to a new path, since some platforms won't load a
+    // library again from a path they've already loaded.
+    num_loaded: usize,
+    // When src/artwork/ last changed as far as we know, and when it had last changed
+    // when the loaded library was built.
+    last_modified: SystemTime,
+    loaded_modified: SystemTime,
+    last_checked: Instant,
+    // With when src/artwork/ had last changed when the build started.
+    build: Option<(Handle, SystemTime)>,
+    // When the rest of src/ and Cargo.toml had last changed when the app started.
+    shared_modified: SystemTime,
+    needs_restart: bool,
+}
+
+impl HotReloader {
+    pub fn from_command_line() -> Option<HotReloader> {
+        if !command_line::has_flag("--hot-reload") {
+            return None;
+        }
+
+        let loaded_folder = built_library_path().with_file_name("hot_reload");
+        if loaded_folder.exists() {
+            fs::remove_dir_all(&loaded_folder).expect("Couldn't clear old hot reload builds.");
+        }
+
+        println!("Building the artwork so we can hot reload it.");
+        build_command()
+            .run()
+            .expect("Couldn't build the artwork for hot reloading.");
+
+        let modified = last_modified(&artwork_folder(), None);
+        let library = load(0).unwrap_or_else(|| {
+            panic!("The artwork was built from different code than the app. Run it again.")
+        });
+
+        Some(HotReloader {
+            library,
+            num_loaded: 1,
+            last_modified: modified,
+            loaded_modified: modified,
+            last_checked: Instant::now(),
+            build: None,
+            shared_modified: shared_last_modified(),
+            needs_restart: false,
+        })
+    }
+
+    pub fn create_artwork(&self, params: CreateArtworkParams) -> Box<dyn Artwork> {
+        let create_artwork: Symbol<CreateArtwork> = unsafe {
+            self.library
+                .get(b"create_artwork")
+                .expect("The hot reloaded library doesn't have a create_artwork function.")
+        };
+
+        create_artwork(params)
+    }
+
+    // Call this every update. When a new build got loaded, this returns the old
+    // library. Anything created by it has to be dropped before it is.
+    pub fn poll(&mut self) -> Option<Library> {
+        if self.needs_restart {
+            return None;
+        }
+
+        if let Some((build, build_modified)) = &self.build {
+            match build.try_wait() {
+                Ok(None) => return None,
+                Ok(Some(_)) => {
+                    self.loaded_modified = *build_modified;
+                    self.build = None;
+                    return self.reload();
+                }
+                Err(error) => {
+                    // The compiler errors already went to the terminal.
+                    println!("Couldn't rebuild the artwork, so we're keeping the old one. {error}");
+                    self.build = None;
+                    return None;
+                }
+            }
+        }
+
+        if self.last_checked.elapsed() < check_interval {
+            return None;
+        }
+        self.last_checked = Instant::now();
+
+        if shared_last_modified() != self.shared_modified {
+            self.stop_reloading();
+            return None;
+        }
+
+        let modified = last_modified(&artwork_folder(), None);
+        if modified > self.last_modified {
+            self.last_modified = modified;
+
+            println!("The artwork changed, rebuilding.");
+            let build = build_command()
+                .start()
+                .expect("Couldn't start building the artwork.");
+            self.build = Some((build, modified));
+        }
+
+        None
+    }
+
+    // Snapshots archive the source on disk, so they'd only match the image when the
+    // loaded library was built from it. Not while a rebuild is still going, after
+    // one failed, or once the rest of src/ changed under the app.
+    pub fn matches_source(&self) -> bool {
+        !self.needs_restart
+            && self.build.is_none()
+            && last_modified(&artwork_folder(), None) == self.loaded_modified
+            && shared_last_modified() == self.shared_modified
+    }
+
+    fn reload(&mut self) -> Option<Library> {
+        let library = load(self.num_loaded);
+        self.num_loaded += 1;
+
+        let library = match library {
+            Some(library) => library,
+            None => {
+                self.stop_reloading();
+                return None;
+            }
+        };
+
+        println!("Reloaded the artwork.");
+
+        Some(std::mem::replace(&mut self.library, library))
+    }
+
+    fn stop_reloading(&mut self) {
+        self.needs_restart = true;
+
+        println!("Code outside the artwork changed, which the app can't reload. Keeping the old artwork until you restart.");
+    }
+}
+
+// The app's statics that the artwork needs to see.
+pub struct SharedGlobals {
+    draw_index: &'static std::sync::Mutex<usize>,
+    artwork_window_id: Option<WindowId>,
+}
+
+impl SharedGlobals {
+    fn from_app() -> SharedGlobals {
+        SharedGlobals {
+            draw_index: loop_drawer::draw_index(),
+            artwork_window_id: get_artwork_window_id(),
+        }
+    }
+
+    // Runs inside the library.
+    pub fn use_in_this_library(self) {
+        loop_drawer::share_draw_index(self.draw_index);
+
+        if let Some(window_id) = self.artwork_window_id {
+            set_artwork_window(window_id);
+        }
+    }
+}
+
+fn build_command() -> duct::Expression {
+    let mut arguments = vec!["build", "--example", library_name];
+    if !cfg!(debug_assertions) {
+        arguments.push("--release");
+    }
+
+    cmd("cargo", arguments).dir(manifest::folder())
+}
+
+fn built_library_path() -> PathBuf {
+    let target_folder = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest::folder().join("target"));
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+
+    target_folder
+        .join(profile)
+        .join("examples")
+        .join(libloading::library_filename(library_name))
+}
+
+// Made by build.rs from everything the app and the library have to agree on.
+pub fn build_hash() -> u64 {
+    env!("HOT_RELOAD_BUILD_HASH")
+        .parse()
+        .expect("build.rs made an invalid build hash.")
+}
+
+// None when the library was built from different code than the app, in which case
+// none of its Rust functions are safe to call.
+fn load(index: usize) -> Option<Library> {
+    let built_path = built_library_path();
+    let loaded_folder = built_path.with_file_name("hot_reload");
+    fs::create_dir_all(&loaded_folder).unwrap();
+
+    let file_name = built_path.file_name().unwrap().to_string_lossy();
+    let loaded_path = loaded_folder.join(format!("{index}_{file_name}"));
+    fs::copy(&built_path, &loaded_path).expect("Couldn't copy the built artwork.");
+
+    // Loading runs no Rust code of the library's own, and build_hash is extern "C",
+    // so both are safe before we know it matches.
+    let library = unsafe { Library::new(&loaded_path) }.expect("Couldn't load the built artwork.");
+
+    {
+        let library_build_hash: Symbol<BuildHash> = unsafe {
+            library
+                .get(b"build_hash")
+                .expect("The hot reloaded library doesn't have a build_hash function.")
+        };
+        if library_build_hash() != build_hash() {
+            return None;
+        }
+    }
+
+    {
+        let share_globals: Symbol<ShareGlobals> = unsafe {
+            library
+                .get(b"share_globals")
+                .expect("The hot reloaded library doesn't have a share_globals function.")
+        };
+        share_globals(SharedGlobals::from_app());
+    }
+
+    Some(library)
+}
+
+fn artwork_folder() -> PathBuf {
+    manifest::folder().join("src").join("artwork")
+}
+
+// The rest of src/ and the manifest, which the library shares with the app.
+fn shared_last_modified() -> SystemTime {
+    let src_modified = last_modified(&manifest::folder().join("src"), Some(&artwork_folder()));
+    let manifest_modified = fs::metadata(manifest::folder().join("Cargo.toml"))
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    src_modified.max(manifest_modified)
+}
+
+fn last_modified(folder: &Path, skipped_folder: Option<&Path>) -> SystemTime {
+    fs::read_dir(folder)
+        .expect("Couldn't read the source folder.")
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+
+            if metadata.is_dir() {
+                if Some(entry.path().as_path()) == skipped_folder {
+                    return None;
+                }
+                Some(last_modified(&entry.path(), skipped_folder))
+            } else {
+                metadata.modified().ok()
+            }
+        })
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
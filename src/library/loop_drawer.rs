@@ -1,9 +1,69 @@
 use crate::prelude::*;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Mutex;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-pub static global_draw_index: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+// Counts every draw since the render started, so it never goes backwards. Read it
+// with draw_index.
+static global_draw_index: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+
+// A hot reloaded library has its own copy of global_draw_index, which the app never
+// advances. So the app hands over its own, see hot_reload::share_globals.
+static shared_draw_index: OnceCell<&'static Mutex<usize>> = OnceCell::new();
+
+pub fn draw_index() -> &'static Mutex<usize> {
+    shared_draw_index
+        .get()
+        .copied()
+        .unwrap_or(&*global_draw_index)
+}
+
+pub fn share_draw_index(draw_index: &'static Mutex<usize>) {
+    shared_draw_index.set(draw_index).ok();
+}
+
+// Every shape for the SVG stays in memory until the render is done, so long renders
+// could run out of it.
+const max_svg_shapes: usize = 1_000_000;
+
+// How many draws happen in each frame, or in each batch when rendering headlessly.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DrawScheduling {
+    // As many draws as fit in a 60th of a second. Feels responsive, but how the
+    // draws get split into frames depends on how fast the machine is.
+    Adaptive,
+    // The same number of draws every frame, so the output only depends on the seed.
+    Fixed { draws_per_batch: usize },
+}
+
+impl DrawScheduling {
+    // Headless renders, --deterministic and --record-frames use fixed batches.
+    // --draws-per-frame changes the batch size.
+    pub fn from_command_line(is_headless: bool) -> DrawScheduling {
+        let is_fixed = is_headless
+            || command_line::has_flag("--deterministic")
+            || command_line::has_flag("--record-frames");
+
+        if !is_fixed {
+            return DrawScheduling::Adaptive;
+        }
+
+        let draws_per_batch = command_line::value_of("--draws-per-frame")
+            .map(|value| {
+                value.parse::<usize>().unwrap_or_else(|_| {
+                    panic!("--draws-per-frame expects a whole number, got {value}.")
+                })
+            })
+            .unwrap_or(10)
+            .max(1);
+
+        DrawScheduling::Fixed { draws_per_batch }
+    }
+}
 
 pub struct LoopDrawer {
     artwork: Box<dyn Artwork>,
@@ -13,6 +73,19 @@ pub struct LoopDrawer {
     num_repeats: usize,
     average_duration_of_each_draw_micros: Option<f64>,
     background_color: Hsl,
+    scheduling: DrawScheduling,
+    // Only kept when we're exporting an SVG.
+    svg_shapes: Option<Vec<Shape>>,
+    frame_recorder: Option<FrameRecorder>,
+    checkpointer: Option<Checkpointer>,
+    // When resuming, we start from this instead of the background color.
+    resumed_canvas_path: Option<PathBuf>,
+    // Waiting for its canvas to be captured from the window before it's saved.
+    pending_checkpoint: Option<Checkpoint>,
+    // Kept between batches, since layers can build up over several of them.
+    offscreen_layers: Rc<RefCell<OffscreenLayers>>,
+    // None unless we're rendering headlessly.
+    num_shapes_drawn_headlessly: Option<usize>,
 }
 
 impl LoopDrawer {
@@ -20,60 +93,276 @@ impl LoopDrawer {
     where
         A: Artwork + 'static,
     {
+        LoopDrawer::from_box(Box::new(artwork))
+    }
+
+    pub fn from_box(artwork: Box<dyn Artwork>) -> Self {
         let num_repeats = artwork.num_repeats();
         let background_color = artwork.background_color();
 
         LoopDrawer {
-            artwork: Box::new(artwork),
+            artwork,
             has_drawn: false,
             start_time: None,
             num_drawn: 0,
             num_repeats,
             average_duration_of_each_draw_micros: None,
             background_color,
+            scheduling: DrawScheduling::Adaptive,
+            svg_shapes: svg::is_enabled().then(Vec::new),
+            frame_recorder: None,
+            checkpointer: None,
+            resumed_canvas_path: None,
+            pending_checkpoint: None,
+            offscreen_layers: Rc::new(RefCell::new(OffscreenLayers::default())),
+            num_shapes_drawn_headlessly: None,
         }
     }
 
+    pub fn scheduling(mut self, scheduling: DrawScheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    pub fn recording_frames(mut self, frame_recorder: Option<FrameRecorder>) -> Self {
+        self.frame_recorder = frame_recorder;
+        self
+    }
+
+    pub fn checkpointing(mut self, checkpointer: Checkpointer) -> Self {
+        self.checkpointer = Some(checkpointer);
+        self
+    }
+
+    // Carries on from a checkpoint instead of starting from scratch.
+    pub fn resuming(mut self, checkpoint: Checkpoint) -> Self {
+        self.num_drawn = checkpoint.num_drawn;
+        self.scheduling = checkpoint.scheduling;
+
+        if let Some(artwork_state) = checkpoint.artwork_state {
+            self.artwork
+                .restore_checkpoint_state(artwork_state)
+                .unwrap_or_else(|error| panic!("Couldn't resume the render. {error}"));
+        }
+
+        for (layer_name, path) in checkpoint.offscreen_layer_paths() {
+            self.offscreen_layers
+                .borrow_mut()
+                .load_png(&layer_name, &path);
+        }
+
+        self.resumed_canvas_path = Some(checkpoint.canvas_path());
+        self
+    }
+
     pub fn draw_artwork_in_a_loop(&mut self, app: &App, model: &Model, frame: Frame) {
         if model.done_rendering {
             return;
         }
 
-        self.start_time.get_or_insert_with(Instant::now);
+        self.save_pending_checkpoint(app);
 
-        self.has_drawn = true;
+        let draw = scaled_to_container(&app.draw(), &model.container);
 
-        let rand = model.snapshot.get_rand();
-        let container = &model.container;
+        // Not elapsed_frames, because restarting starts from a blank canvas again.
+        if !self.has_drawn {
+            match &self.resumed_canvas_path {
+                Some(path) => draw_resumed_canvas(app, path),
+                None => {
+                    draw.background().color(self.background_color);
+                }
+            }
+        }
+
+        self.offscreen_layers.borrow_mut().start_frame();
+        let canvas = Canvas::windowed(draw.clone(), &artwork_window(app))
+            .recording(self.svg_shapes.is_some())
+            .offscreen_layers(&self.offscreen_layers);
+        self.draw_batch(Some(app), model, &draw, &canvas);
+        self.keep_svg_shapes(canvas.take_recorded_shapes());
+
+        draw.to_frame(app, &frame).unwrap();
+
+        // These capture the frame we just drew.
+        if let Some(path) = self.next_recorded_frame_path() {
+            artwork_window(app).capture_frame(path);
+        }
+
+        // Captures happen in the background, so the checkpoint waits for the next frame.
+        if let Some(checkpoint) = self.checkpoint_if_due(model) {
+            artwork_window(app).capture_frame(checkpoint.canvas_path());
+            self.pending_checkpoint = Some(checkpoint);
+        }
+    }
+
+    pub fn draw_artwork_headlessly(&mut self, model: &Model, rasterizer: &mut Rasterizer) {
+        if model.done_rendering {
+            return;
+        }
 
-        // Scale and translate the draw instance so that we can use normalized points.
-        let draw = app
-            .draw()
-            .scale_axes(vec3(container.w(), container.h(), container.w()))
-            .translate(vec3(-0.5, -0.5, -0.5));
-
-        let mut params = DrawParams {
-            app,
-            model,
-            rand: &rand,
-            draw: &draw,
-            container,
-            progress_through_whole_drawing: 0.0,
+        if !self.has_drawn {
+            match &self.resumed_canvas_path {
+                Some(path) => rasterizer.load_png(path),
+                None => rasterizer.fill_background(self.background_color),
+            }
+        }
+
+        // Only shapes drawn through the canvas end up in the image. Anything drawn
+        // directly with nannou goes to this draw instance, which we throw away.
+        let draw = scaled_to_container(&Draw::new(), &model.container);
+        let canvas = Canvas::headless().offscreen_layers(&self.offscreen_layers);
+        self.draw_batch(None, model, &draw, &canvas);
+
+        let shapes = canvas.take_recorded_shapes();
+        *self.num_shapes_drawn_headlessly.get_or_insert(0) += shapes.len();
+        rasterizer.rasterize(&shapes);
+        self.keep_svg_shapes(shapes);
+
+        if let Some(path) = self.next_recorded_frame_path() {
+            rasterizer
+                .to_rgba_image()
+                .save(path)
+                .expect("Couldn't save a recorded frame.");
+        }
+
+        if let Some(checkpoint) = self.checkpoint_if_due(model) {
+            rasterizer.save_png(&checkpoint.canvas_path());
+            self.save_checkpoint(checkpoint);
+        }
+    }
+
+    pub fn num_repeats(&self) -> usize {
+        self.num_repeats
+    }
+
+    pub fn post_processing(&self) -> PostProcessing {
+        self.artwork.post_processing()
+    }
+
+    pub fn render_duration(&self) -> Option<Duration> {
+        self.start_time.map(|start_time| start_time.elapsed())
+    }
+
+    pub fn save_svg(&self, image_name: &str) {
+        if let Some(svg_shapes) = &self.svg_shapes {
+            let path = snapshot::images_folder_path().join(format!("{image_name}.svg"));
+            svg::save(svg_shapes, &path);
+        }
+    }
+
+    // Saves the offscreen layers, but the canvas still needs saving before the
+    // checkpoint does.
+    fn checkpoint_if_due(&mut self, model: &Model) -> Option<Checkpoint> {
+        let checkpointer = self.checkpointer.as_mut()?;
+
+        let is_finished = self.num_drawn >= self.num_repeats;
+        if is_finished || !checkpointer.is_due() {
+            return None;
+        }
+
+        let offscreen_layers = self.offscreen_layers.borrow();
+        let offscreen_layer_file_names = offscreen_layers
+            .names()
+            .into_iter()
+            .enumerate()
+            .map(|(layer_index, layer_name)| {
+                let file_name = Checkpoint::offscreen_layer_file_name(layer_index, self.num_drawn);
+                (layer_name, file_name)
+            })
+            .collect();
+
+        let checkpoint = Checkpoint {
+            snapshot_name: checkpointer.snapshot_name.clone(),
+            num_drawn: self.num_drawn,
+            scheduling: self.scheduling,
+            rand_state: model.rand.state(),
+            artwork_state: self.artwork.checkpoint_state(),
+            parameters: model.parameters.values(),
+            canvas_file_name: Checkpoint::canvas_file_name(self.num_drawn),
+            offscreen_layer_file_names,
         };
+        checkpointer.mark_saved();
 
-        if app.elapsed_frames() == 0 {
-            draw.background().color(self.background_color);
+        for (layer_name, path) in checkpoint.offscreen_layer_paths() {
+            offscreen_layers.save_png(&layer_name, &path);
         }
 
+        Some(checkpoint)
+    }
+
+    fn save_pending_checkpoint(&mut self, app: &App) {
+        if let Some(checkpoint) = self.pending_checkpoint.take() {
+            artwork_window(app)
+                .await_capture_frame_jobs()
+                .expect("Couldn't capture the canvas for a checkpoint.");
+            self.save_checkpoint(checkpoint);
+        }
+    }
+
+    fn save_checkpoint(&self, checkpoint: Checkpoint) {
+        checkpoint.save();
+        println!("Saved a checkpoint after {} draws.", checkpoint.num_drawn);
+    }
+
+    fn next_recorded_frame_path(&mut self) -> Option<PathBuf> {
+        let is_last_batch = self.num_drawn >= self.num_repeats;
+
+        self.frame_recorder
+            .as_mut()
+            .and_then(|frame_recorder| frame_recorder.next_frame_path(is_last_batch))
+    }
+
+    // Composited offscreen layers are window-sized pixmaps that the SVG leaves out
+    // anyway, so there's no point holding on to them.
+    fn keep_svg_shapes(&mut self, shapes: Vec<Shape>) {
+        let svg_shapes = match &mut self.svg_shapes {
+            Some(svg_shapes) => svg_shapes,
+            None => return,
+        };
+
+        let was_full = svg_shapes.len() >= max_svg_shapes;
+        let room = max_svg_shapes.saturating_sub(svg_shapes.len());
+
+        let mut shapes = shapes
+            .into_iter()
+            .filter(|shape| !matches!(shape, Shape::Pixels { .. }))
+            .peekable();
+        svg_shapes.extend(shapes.by_ref().take(room));
+
+        if !was_full && shapes.peek().is_some() {
+            println!("The SVG has {max_svg_shapes} shapes already, so it'll leave out the rest.");
+        }
+    }
+
+    fn draw_batch(&mut self, app: Option<&App>, model: &Model, draw: &Draw, canvas: &Canvas) {
+        self.start_time.get_or_insert_with(Instant::now);
+
+        self.has_drawn = true;
+
+        let rand = model.snapshot.get_rand();
+        let container = &model.container;
+
         let num_draws_this_frame = self.num_draws_this_frame();
         let max_draw_index = self.num_repeats - 1;
         let draw_start = Instant::now();
 
         for current_draw_index in 0..num_draws_this_frame {
-            *global_draw_index.lock().unwrap() = current_draw_index;
-
             let total_draw_index = current_draw_index + self.num_drawn;
-            params.progress_through_whole_drawing = total_draw_index as f32 / max_draw_index as f32;
+            *draw_index().lock().unwrap() = total_draw_index;
+
+            // Based on the draw index rather than the frame, so the image doesn't
+            // depend on how many draws fit in each frame.
+            let draw_rand = rand.for_draw(total_draw_index);
+
+            let params = DrawParams {
+                app,
+                model,
+                rand: &draw_rand,
+                draw,
+                canvas,
+                container,
+                progress_through_whole_drawing: total_draw_index as f32 / max_draw_index as f32,
+            };
 
             self.artwork.draw(&params);
             // std::thread::sleep(std::time::Duration::from_millis(8));
@@ -85,8 +374,6 @@ impl LoopDrawer {
         self.average_duration_of_each_draw_micros = Some(average_duration_of_each_draw_micros);
 
         self.num_drawn += num_draws_this_frame;
-
-        draw.to_frame(app, &frame).unwrap();
     }
 
     pub fn update(&mut self) -> DoneDrawing {
@@ -95,9 +382,22 @@ impl LoopDrawer {
             return DoneDrawing::No;
         }
 
-        if self.num_drawn >= self.num_repeats {
+        let is_done = self.num_drawn >= self.num_repeats || self.artwork.done_drawing().to_bool();
+
+        if is_done {
             let elapsed = self.start_time.unwrap().elapsed();
             println!("Done drawing. Time taken: {:?}.", elapsed);
+
+            if let Some(0) = self.num_shapes_drawn_headlessly {
+                println!("Nothing was drawn on params.canvas, so the image is blank. Headless renders leave out anything drawn on params.draw.");
+            }
+
+            // Nothing to resume anymore.
+            self.pending_checkpoint = None;
+            if let Some(checkpointer) = &self.checkpointer {
+                Checkpoint::remove(&checkpointer.snapshot_name);
+            }
+
             DoneDrawing::Yes
         } else {
             DoneDrawing::No
@@ -115,6 +415,10 @@ impl LoopDrawer {
     }
 
     fn num_draws_per_frame(&self) -> usize {
+        if let DrawScheduling::Fixed { draws_per_batch } = self.scheduling {
+            return draws_per_batch;
+        }
+
         match self.average_duration_of_each_draw_micros {
             None => 1, // If we haven't drawn yet, perform a few draws to see how long it takes.
             Some(average_duration_of_each_draw_micros) => {
@@ -128,6 +432,142 @@ impl LoopDrawer {
     }
 }
 
+// Draws the canvas saved in a checkpoint over the whole window.
+fn draw_resumed_canvas(app: &App, path: &Path) {
+    let texture = nannou::wgpu::Texture::from_path(app, path)
+        .expect("Couldn't load the canvas saved in the checkpoint.");
+    let [window_width, window_height] = get_window_dimensions();
+
+    app.draw()
+        .texture(&texture)
+        .w_h(window_width, window_height);
+}
+
+// Scale and translate the draw instance so that we can use normalized points.
+fn scaled_to_container(draw: &Draw, container: &Rect) -> Draw {
+    draw.scale_axes(vec3(container.w(), container.h(), container.w()))
+        .translate(vec3(-0.5, -0.5, -0.5))
+}
+
 const micros_in_second: f64 = 1_000_000.0;
 const frames_per_second: f64 = 60.0;
 const target_frame_duration_micros: f64 = micros_in_second / frames_per_second;
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use nannou::image::RgbaImage;
+    use std::time::Duration;
+
+    // Uses both kinds of rand, since either could pick up on how the draws get
+    // split into batches.
+    struct Dots;
+
+    impl Artwork for Dots {
+        fn draw(&mut self, params: &DrawParams) {
+            let center = params.rand_that_changes_every_frame().xy();
+            let radius = params
+                .rand_that_stays_the_same_every_frame()
+                .range_f32(&(0.01..=0.05));
+
+            params
+                .canvas
+                .ellipse(center, vec2(radius, radius), soft_black());
+        }
+
+        fn num_repeats(&self) -> usize {
+            200
+        }
+
+        fn background_color(&self) -> Hsl {
+            soft_white()
+        }
+    }
+
+    fn render(seed: u64, scheduling: DrawScheduling, fake_draw_duration_micros: f64) -> RgbaImage {
+        let model = Model::unsaved(seed);
+        let mut rasterizer = Rasterizer::new(&model.container);
+        let mut loop_drawer = LoopDrawer::new(Dots).scheduling(scheduling);
+
+        loop {
+            loop_drawer.draw_artwork_headlessly(&model, &mut rasterizer);
+
+            // Pretend the machine is faster or slower than it really is.
+            loop_drawer.average_duration_of_each_draw_micros = Some(fake_draw_duration_micros);
+
+            if let DoneDrawing::Yes = loop_drawer.update() {
+                break;
+            }
+        }
+
+        rasterizer.to_rgba_image()
+    }
+
+    #[test]
+    fn batch_sizes_dont_change_the_image() {
+        let one_at_a_time = render(42, DrawScheduling::Fixed { draws_per_batch: 1 }, 1.0);
+
+        for (scheduling, fake_draw_duration_micros) in [
+            (DrawScheduling::Fixed { draws_per_batch: 7 }, 1.0),
+            (DrawScheduling::Adaptive, 1.0),
+            (DrawScheduling::Adaptive, 5_000.0),
+        ] {
+            let image = render(42, scheduling, fake_draw_duration_micros);
+
+            assert!(
+                image == one_at_a_time,
+                "{scheduling:?} at {fake_draw_duration_micros} microseconds a draw rendered a different image."
+            );
+        }
+    }
+
+    #[test]
+    fn different_seeds_render_different_images() {
+        let scheduling = DrawScheduling::Fixed { draws_per_batch: 7 };
+
+        let first = render(42, scheduling, 1.0);
+        let second = render(43, scheduling, 1.0);
+
+        assert!(first != second, "Different seeds rendered the same image.");
+    }
+
+    #[test]
+    fn resuming_from_a_checkpoint_renders_the_same_image() {
+        let scheduling = DrawScheduling::Fixed { draws_per_batch: 7 };
+        let uninterrupted = render(42, scheduling, 1.0);
+
+        // Checkpoints after every batch, then stops partway through like a crash.
+        let model = Model::unsaved(42);
+        let snapshot_name = model.snapshot.source_code_folder_name.clone();
+        let mut rasterizer = Rasterizer::new(&model.container);
+        let mut loop_drawer = LoopDrawer::new(Dots)
+            .scheduling(scheduling)
+            .checkpointing(Checkpointer::new(&snapshot_name, Duration::ZERO));
+
+        while loop_drawer.num_drawn < 100 {
+            loop_drawer.draw_artwork_headlessly(&model, &mut rasterizer);
+        }
+
+        let checkpoint = Checkpoint::load(&snapshot_name).expect("Didn't save a checkpoint.");
+        assert_eq!(checkpoint.num_drawn, loop_drawer.num_drawn);
+
+        let mut model = Model::unsaved(42);
+        model.rand = Rand::from_state(&checkpoint.rand_state);
+        let mut rasterizer = Rasterizer::new(&model.container);
+        let mut loop_drawer = LoopDrawer::new(Dots).resuming(checkpoint);
+
+        loop {
+            loop_drawer.draw_artwork_headlessly(&model, &mut rasterizer);
+
+            if let DoneDrawing::Yes = loop_drawer.update() {
+                break;
+            }
+        }
+        Checkpoint::remove(&snapshot_name);
+
+        assert!(
+            rasterizer.to_rgba_image() == uninterrupted,
+            "The resumed render doesn't match the uninterrupted one."
+        );
+    }
+}
@@ -1,30 +1,57 @@
 pub mod averaging_window;
 pub use averaging_window::*;
 
+pub mod canvas;
+pub use canvas::*;
+
+pub mod checkpoint;
+pub use checkpoint::*;
+
 pub mod circle;
 pub use circle::*;
 
+pub mod color_config;
+pub use color_config::*;
+
 pub mod color_predictor;
 pub use color_predictor::*;
 
+pub mod command_line;
+
 pub mod context_generators;
 pub use self::context_generators::*;
 
 pub mod create_change_tracker;
 pub use create_change_tracker::create_change_tracker;
 
+pub mod curl_noise;
+
+pub mod distributions;
+pub use distributions::*;
+
 pub mod extensions;
 pub use extensions::*;
 
+pub mod fractal_noise;
+pub use fractal_noise::*;
+
 // pub mod old_generators;
 // pub use old_generators::*;
 
+pub mod frame_recorder;
+pub use frame_recorder::*;
+
 pub mod gradient;
 pub use gradient::*;
 
 pub mod grid;
 pub use grid::*;
 
+pub mod headless;
+
+pub mod hot_reload;
+pub use hot_reload::*;
+
 pub mod id_generator;
 pub use id_generator::*;
 
@@ -46,12 +73,30 @@ pub use open_ai::*;
 pub mod ordered_point2;
 pub use ordered_point2::*;
 
+pub mod parameter_panel;
+pub use parameter_panel::*;
+
+pub mod parameters;
+pub use parameters::*;
+
+pub mod plotter;
+pub use plotter::*;
+
 pub mod point_direction;
 pub use point_direction::*;
 
+pub mod post_processing;
+pub use post_processing::*;
+
+pub mod rand_streams;
+pub use rand_streams::*;
+
 pub mod rect_grid;
 pub use rect_grid::*;
 
+pub mod sampling;
+pub use sampling::*;
+
 pub mod shell_bundle;
 pub use shell_bundle::*;
 
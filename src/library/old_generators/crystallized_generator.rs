@@ -31,7 +31,7 @@ where
     }
 
     fn update_if_in_new_draw_call(&mut self, rand: &Rand, input: Input) {
-        let current_draw_index = *crate::library::loop_drawer::global_draw_index
+        let current_draw_index = *crate::library::loop_drawer::draw_index()
             .lock()
             .unwrap();
 
@@ -0,0 +1,126 @@
+use crate::prelude::*;
+use nannou::winit::event::WindowEvent;
+use nannou_egui::{egui, Egui};
+
+// A second window with a control for each of the artwork's parameters. Changing
+// one starts the render over with the same seed.
+pub struct ParameterPanel {
+    egui: Egui,
+}
+
+impl ParameterPanel {
+    pub fn open(app: &App) -> ParameterPanel {
+        let window_id = app
+            .new_window()
+            .title("Parameters")
+            .size(320, 480)
+            .view(view)
+            .raw_event(raw_window_event)
+            .build()
+            .unwrap();
+
+        let window = app.window(window_id).unwrap();
+
+        ParameterPanel {
+            egui: Egui::from_window(&window),
+        }
+    }
+
+    // Returns whether any values changed.
+    pub fn update(&mut self, update: &Update, parameters: &mut Parameters) -> bool {
+        self.egui.set_elapsed_time(update.since_start);
+        let context = self.egui.begin_frame();
+
+        let mut did_change = false;
+
+        egui::CentralPanel::default().show(&context, |ui| {
+            for parameter in parameters.declared() {
+                let mut value = parameter.value;
+                show_control(ui, &parameter, &mut value);
+
+                if value != parameter.value {
+                    parameters.set(&parameter.name, value);
+                    did_change = true;
+                }
+            }
+        });
+
+        did_change
+    }
+}
+
+fn show_control(ui: &mut egui::Ui, parameter: &Parameter, value: &mut ParameterValue) {
+    let name = parameter.name.as_str();
+
+    match (&parameter.control, value) {
+        (ParameterControl::Slider(range), ParameterValue::Number(number)) => {
+            ui.add(egui::Slider::new(number, range.clone()).text(name));
+        }
+
+        (ParameterControl::WholeNumberSlider(range), ParameterValue::WholeNumber(number)) => {
+            ui.add(egui::Slider::new(number, range.clone()).text(name));
+        }
+
+        (ParameterControl::Toggle, ParameterValue::Toggle(is_on)) => {
+            ui.checkbox(is_on, name);
+        }
+
+        (
+            ParameterControl::ColorPicker,
+            ParameterValue::Color {
+                hue,
+                saturation,
+                lightness,
+            },
+        ) => {
+            // egui's color picker works in HSV, so convert there and back.
+            let value = *lightness + *saturation * lightness.min(1.0 - *lightness);
+            let hsv_saturation = if value == 0.0 {
+                0.0
+            } else {
+                2.0 * (1.0 - *lightness / value)
+            };
+            let mut hsva = egui::color::Hsva::new(*hue, hsv_saturation, value, 1.0);
+
+            ui.horizontal(|ui| {
+                ui.color_edit_button_hsva(&mut hsva);
+                ui.label(name);
+            });
+
+            let new_lightness = hsva.v * (1.0 - hsva.s / 2.0);
+            let new_saturation = if new_lightness == 0.0 || new_lightness == 1.0 {
+                0.0
+            } else {
+                (hsva.v - new_lightness) / new_lightness.min(1.0 - new_lightness)
+            };
+
+            // Only write back real changes, so rounding errors don't restart the
+            // render every frame.
+            let did_change = (hsva.h - *hue).abs() > 1e-4
+                || (new_saturation - *saturation).abs() > 1e-4
+                || (new_lightness - *lightness).abs() > 1e-4;
+
+            if did_change {
+                *hue = hsva.h;
+                *saturation = new_saturation;
+                *lightness = new_lightness;
+            }
+        }
+
+        (control, value) => {
+            panic!("{name} is a {control:?}, which can't show a {value:?}.");
+        }
+    }
+}
+
+fn view(_app: &App, model: &Model, frame: Frame) {
+    if let Some(parameter_panel) = &model.parameter_panel {
+        parameter_panel.egui.draw_to_frame(&frame).unwrap();
+    }
+}
+
+fn raw_window_event(_app: &App, model: &mut Model, event: &WindowEvent) {
+    if let Some(parameter_panel) = &mut model.parameter_panel {
+        parameter_panel.egui.handle_raw_event(event);
+    }
+}
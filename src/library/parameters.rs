@@ -0,0 +1,221 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::mem::discriminant;
+use std::ops::RangeInclusive;
+
+// Settings an artwork reads by name instead of hard-coding, so they can be tweaked
+// while the app runs without recompiling (and without a new snapshot for every
+// tweak). The artwork declares each one with a default when it's created. Whatever
+// values it ends up using get saved in snapshot.json.
+//
+//   let num_repeats = params.parameters.whole_number_slider("num_repeats", 100, 1..=1000);
+#[derive(Default)]
+pub struct Parameters {
+    // In the order the artwork declared them, which is the order the panel shows.
+    declared: RefCell<Vec<Parameter>>,
+    // Values that win over the defaults, e.g. ones changed in the panel.
+    overrides: BTreeMap<String, ParameterValue>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub control: ParameterControl,
+    pub value: ParameterValue,
+}
+
+#[derive(Clone, Debug)]
+pub enum ParameterControl {
+    Slider(RangeInclusive<f32>),
+    WholeNumberSlider(RangeInclusive<usize>),
+    Toggle,
+    ColorPicker,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ParameterValue {
+    Number(f32),
+    WholeNumber(usize),
+    Toggle(bool),
+    // The hue is normalized, like the hsl function expects.
+    Color {
+        hue: f32,
+        saturation: f32,
+        lightness: f32,
+    },
+}
+
+impl Parameters {
+    // --parameters <path> loads values from a JSON file, e.g. the one --replay
+    // writes from the original snapshot.
+    pub fn from_command_line() -> Parameters {
+        let overrides = match command_line::value_of("--parameters") {
+            Some(path) => {
+                let json = fs::read_to_string(&path)
+                    .unwrap_or_else(|error| panic!("Couldn't read {path}: {error}"));
+                serde_json::from_str(&json)
+                    .unwrap_or_else(|error| panic!("Couldn't parse {path}: {error}"))
+            }
+            None => BTreeMap::new(),
+        };
+
+        Parameters {
+            declared: RefCell::new(vec![]),
+            overrides,
+        }
+    }
+
+    pub fn slider(&self, name: &str, default: f32, range: RangeInclusive<f32>) -> f32 {
+        let value = self.declare(
+            name,
+            ParameterControl::Slider(range),
+            ParameterValue::Number(default),
+        );
+
+        match value {
+            ParameterValue::Number(number) => number,
+            _ => default,
+        }
+    }
+
+    pub fn whole_number_slider(
+        &self,
+        name: &str,
+        default: usize,
+        range: RangeInclusive<usize>,
+    ) -> usize {
+        let value = self.declare(
+            name,
+            ParameterControl::WholeNumberSlider(range),
+            ParameterValue::WholeNumber(default),
+        );
+
+        match value {
+            ParameterValue::WholeNumber(number) => number,
+            _ => default,
+        }
+    }
+
+    pub fn toggle(&self, name: &str, default: bool) -> bool {
+        let value = self.declare(
+            name,
+            ParameterControl::Toggle,
+            ParameterValue::Toggle(default),
+        );
+
+        match value {
+            ParameterValue::Toggle(is_on) => is_on,
+            _ => default,
+        }
+    }
+
+    pub fn color(&self, name: &str, default: Hsl) -> Hsl {
+        let default_value = ParameterValue::Color {
+            hue: default.normalized_hue(),
+            saturation: default.saturation,
+            lightness: default.lightness,
+        };
+
+        let value = self.declare(name, ParameterControl::ColorPicker, default_value);
+
+        // Only convert when the color changed, so the default comes back exactly as it
+        // went in.
+        match value {
+            ParameterValue::Color {
+                hue,
+                saturation,
+                lightness,
+            } if value != default_value => hsl(hue, saturation, lightness),
+            _ => default,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declared.borrow().is_empty()
+    }
+
+    pub fn declared(&self) -> Vec<Parameter> {
+        self.declared.borrow().clone()
+    }
+
+    // The values the artwork is using right now, to save in the snapshot.
+    pub fn values(&self) -> BTreeMap<String, ParameterValue> {
+        self.declared
+            .borrow()
+            .iter()
+            .map(|parameter| (parameter.name.clone(), parameter.value))
+            .collect()
+    }
+
+    pub fn set(&mut self, name: &str, value: ParameterValue) {
+        self.overrides.insert(name.to_string(), value);
+    }
+
+    pub fn set_all(&mut self, values: BTreeMap<String, ParameterValue>) {
+        self.overrides.extend(values);
+    }
+
+    // Call this before creating the artwork again, so it can declare everything
+    // from scratch. Values that were changed stick around.
+    pub fn clear_declarations(&self) {
+        self.declared.borrow_mut().clear();
+    }
+
+    fn declare(
+        &self,
+        name: &str,
+        control: ParameterControl,
+        default: ParameterValue,
+    ) -> ParameterValue {
+        // An override of the wrong kind means the artwork's code changed since it was
+        // saved, so we go with the default.
+        let value = self
+            .overrides
+            .get(name)
+            .copied()
+            .filter(|value| discriminant(value) == discriminant(&default))
+            .unwrap_or(default);
+
+        let mut declared = self.declared.borrow_mut();
+        match declared.iter().find(|parameter| parameter.name == name) {
+            Some(parameter) => parameter.value,
+            None => {
+                declared.push(Parameter {
+                    name: name.to_string(),
+                    control,
+                    value,
+                });
+                value
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn changed_values_win_over_defaults_and_get_recorded() {
+        let mut parameters = Parameters::default();
+        assert_eq!(parameters.slider("radius", 0.5, 0.0..=1.0), 0.5);
+
+        parameters.set("radius", ParameterValue::Number(0.25));
+        // The wrong kind of value, e.g. from a snapshot saved before the code changed.
+        parameters.set("num_circles", ParameterValue::Toggle(true));
+
+        parameters.clear_declarations();
+        assert_eq!(parameters.slider("radius", 0.5, 0.0..=1.0), 0.25);
+        assert_eq!(
+            parameters.whole_number_slider("num_circles", 10, 1..=20),
+            10
+        );
+
+        let values = parameters.values();
+        assert_eq!(values["radius"], ParameterValue::Number(0.25));
+        assert_eq!(values["num_circles"], ParameterValue::WholeNumber(10));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+// Pen plotters spend a lot of time lifting the pen and moving it to the start of the
+// next path. This reorders, flips, merges and filters paths so the pen spends as
+// little time as possible in the air.
+//
+// Works on plain Vec<Path2>s so streamlines, contours, L-systems, dashes and anything
+// recorded on the canvas can all be fed through it.
+pub struct PathOptimizer {
+    // Paths whose ends are closer than this get joined into one path.
+    merge_distance: NormalizedF32,
+    // Segments shorter than this get taken out of each path. Paths that are still
+    // shorter than this once they've been merged get dropped.
+    min_length: NormalizedF32,
+    // Where the pen starts.
+    start: Point2,
+}
+
+pub fn path_optimizer() -> PathOptimizer {
+    PathOptimizer {
+        merge_distance: 0.0005,
+        min_length: 0.0005,
+        start: pt2(0.0, 1.0), // Plotters usually start in the top left corner.
+    }
+}
+
+impl PathOptimizer {
+    pub fn merge_distance(mut self, merge_distance: NormalizedF32) -> Self {
+        self.merge_distance = merge_distance;
+        self
+    }
+
+    pub fn min_length(mut self, min_length: NormalizedF32) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn start(mut self, start: Point2) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn optimize(&self, paths: Vec<Path2>) -> OptimizedPaths {
+        let before = PlotDistances::measure(&paths, &self.start);
+
+        let simplified_paths = paths
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .map(|path| self.remove_short_segments(path))
+            .collect();
+
+        let sorted_paths = self.sort_by_nearest_neighbor(simplified_paths);
+        let merged_paths = self.merge_touching(sorted_paths);
+
+        // Short paths might have been merged into longer ones, so this goes last.
+        let long_enough_paths: Vec<Path2> = merged_paths
+            .into_iter()
+            .filter(|path| path.len() >= 2 && path.walking_length() >= self.min_length)
+            .collect();
+
+        let after = PlotDistances::measure(&long_enough_paths, &self.start);
+
+        OptimizedPaths {
+            paths: long_enough_paths,
+            before,
+            after,
+        }
+    }
+
+    // Optimizes each layer separately, e.g. one layer per pen color.
+    pub fn optimize_layers<Layer>(&self, paths: Vec<(Layer, Path2)>) -> Vec<(Layer, OptimizedPaths)>
+    where
+        Layer: Eq + Hash + Clone,
+    {
+        // Keep layers in the order they first appeared, so the output is deterministic.
+        let mut layer_order: Vec<Layer> = vec![];
+        let mut paths_by_layer: HashMap<Layer, Vec<Path2>> = HashMap::new();
+
+        for (layer, path) in paths {
+            if !paths_by_layer.contains_key(&layer) {
+                layer_order.push(layer.clone());
+            }
+
+            paths_by_layer.entry(layer).or_default().push(path);
+        }
+
+        layer_order
+            .into_iter()
+            .map(|layer| {
+                let paths = paths_by_layer.remove(&layer).unwrap();
+                (layer, self.optimize(paths))
+            })
+            .collect()
+    }
+
+    // Skips points that are too close to the last one we kept. The ends stay where
+    // they are, so the path still meets whatever it touched.
+    fn remove_short_segments(&self, path: Path2) -> Path2 {
+        let last = *path.last().unwrap();
+        let mut kept: Path2 = Vec::with_capacity(path.len());
+
+        for point in path {
+            match kept.last() {
+                Some(previous) if previous.distance(point) < self.min_length => {}
+                _ => kept.push(point),
+            }
+        }
+
+        if *kept.last().unwrap() != last {
+            if kept.len() >= 2 {
+                kept.pop();
+            }
+            kept.push(last);
+        }
+
+        kept
+    }
+
+    // Greedily picks whichever remaining path has an end closest to the pen,
+    // flipping it if its last point is the closer one.
+    fn sort_by_nearest_neighbor(&self, paths: Vec<Path2>) -> Vec<Path2> {
+        let mut remaining: Vec<Option<Path2>> = paths.into_iter().map(Some).collect();
+        let mut sorted = Vec::with_capacity(remaining.len());
+        let mut pen = self.start;
+
+        for _ in 0..remaining.len() {
+            let mut closest_index = 0;
+            let mut closest_distance = f32::MAX;
+            let mut closest_is_reversed = false;
+
+            for (index, maybe_path) in remaining.iter().enumerate() {
+                let path = match maybe_path {
+                    Some(path) => path,
+                    None => continue,
+                };
+
+                let distance_to_first = pen.distance(*path.first().unwrap());
+                if distance_to_first < closest_distance {
+                    closest_index = index;
+                    closest_distance = distance_to_first;
+                    closest_is_reversed = false;
+                }
+
+                let distance_to_last = pen.distance(*path.last().unwrap());
+                if distance_to_last < closest_distance {
+                    closest_index = index;
+                    closest_distance = distance_to_last;
+                    closest_is_reversed = true;
+                }
+            }
+
+            let mut closest_path = remaining[closest_index].take().unwrap();
+            if closest_is_reversed {
+                closest_path.reverse();
+            }
+
+            pen = *closest_path.last().unwrap();
+            sorted.push(closest_path);
+        }
+
+        sorted
+    }
+
+    fn merge_touching(&self, sorted_paths: Vec<Path2>) -> Vec<Path2> {
+        let mut merged: Vec<Path2> = Vec::with_capacity(sorted_paths.len());
+
+        for path in sorted_paths {
+            match merged.last_mut() {
+                Some(previous_path)
+                    if previous_path.last().unwrap().distance(path[0]) <= self.merge_distance =>
+                {
+                    previous_path.extend(path.into_iter().skip(1));
+                }
+                _ => merged.push(path),
+            }
+        }
+
+        merged
+    }
+}
+
+pub struct OptimizedPaths {
+    pub paths: Vec<Path2>,
+    pub before: PlotDistances,
+    pub after: PlotDistances,
+}
+
+impl fmt::Display for OptimizedPaths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} paths. Pen down: {:.3} -> {:.3}. Pen up: {:.3} -> {:.3}.",
+            self.paths.len(),
+            self.before.pen_down,
+            self.after.pen_down,
+            self.before.pen_up,
+            self.after.pen_up
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlotDistances {
+    pub pen_down: f32,
+    pub pen_up: f32,
+}
+
+impl PlotDistances {
+    pub fn measure(paths: &[Path2], start: &Point2) -> PlotDistances {
+        let mut pen_down = 0.0;
+        let mut pen_up = 0.0;
+        let mut pen = *start;
+
+        for path in paths {
+            let (first, last) = match (path.first(), path.last()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => continue,
+            };
+
+            pen_up += pen.distance(*first);
+            pen_down += path.walking_length();
+            pen = *last;
+        }
+
+        PlotDistances { pen_down, pen_up }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn drops_paths_that_are_too_short() {
+        let paths = vec![
+            vec![pt2(0.0, 0.0), pt2(0.5, 0.0)],
+            vec![pt2(0.0, 0.5), pt2(0.0001, 0.5)],
+            vec![pt2(0.9, 0.9)],
+        ];
+
+        let optimized = path_optimizer().min_length(0.01).optimize(paths);
+
+        assert_eq!(optimized.paths.len(), 1);
+    }
+
+    #[test]
+    fn removes_short_segments_but_keeps_the_ends() {
+        let paths = vec![vec![
+            pt2(0.0, 0.0),
+            pt2(0.001, 0.0),
+            pt2(0.5, 0.0),
+            pt2(0.5, 0.001),
+        ]];
+
+        let optimized = path_optimizer().min_length(0.01).optimize(paths);
+
+        assert_eq!(optimized.paths, vec![vec![pt2(0.0, 0.0), pt2(0.5, 0.001)]]);
+    }
+
+    #[test]
+    fn merges_touching_paths_even_if_they_point_in_opposite_directions() {
+        let paths = vec![
+            vec![pt2(0.0, 1.0), pt2(0.5, 1.0)],
+            vec![pt2(1.0, 1.0), pt2(0.5, 1.0)],
+        ];
+
+        let optimized = path_optimizer().optimize(paths);
+
+        assert_eq!(
+            optimized.paths,
+            vec![vec![pt2(0.0, 1.0), pt2(0.5, 1.0), pt2(1.0, 1.0)]]
+        );
+        assert_eq!(optimized.after.pen_up, 0.0);
+    }
+
+    #[test]
+    fn reordering_reduces_pen_up_distance() {
+        let paths = vec![
+            vec![pt2(0.0, 1.0), pt2(0.1, 1.0)],
+            vec![pt2(0.9, 0.0), pt2(1.0, 0.0)],
+            vec![pt2(0.2, 1.0), pt2(0.3, 1.0)],
+            vec![pt2(0.7, 0.0), pt2(0.8, 0.0)],
+        ];
+
+        let optimized = path_optimizer().optimize(paths);
+
+        assert!(optimized.after.pen_up < optimized.before.pen_up);
+        assert!((optimized.after.pen_down - optimized.before.pen_down).abs() < 0.0001);
+    }
+
+    #[test]
+    fn optimizes_layers_separately() {
+        let paths = vec![
+            ("red", vec![pt2(0.0, 0.0), pt2(0.5, 0.0)]),
+            ("blue", vec![pt2(0.0, 0.5), pt2(0.5, 0.5)]),
+            ("red", vec![pt2(0.5, 0.0), pt2(1.0, 0.0)]),
+        ];
+
+        let layers = path_optimizer().optimize_layers(paths);
+
+        let layer_names: Vec<_> = layers.iter().map(|(layer, _)| *layer).collect();
+        assert_eq!(layer_names, vec!["red", "blue"]);
+        assert_eq!(layers[0].1.paths.len(), 1);
+    }
+}
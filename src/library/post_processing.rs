@@ -0,0 +1,686 @@
+use crate::prelude::*;
+use nannou::color::{FromColor, Srgb};
+use nannou::image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Filters that get applied to captured images right before they're saved, e.g.
+//
+//   post_processing()
+//       .paper_texture(0.06, 150.0)
+//       .film_grain(0.03)
+//       .vignette(0.3, 0.5)
+//
+// Artworks return theirs from Artwork::post_processing, and --post-process <path to
+// a .json or .toml file> replaces it:
+//
+//   [[filters]]
+//   type = "dither"
+//   method = "floyd_steinberg"
+//   levels = 4
+//
+// The filters run in order and get saved in snapshot.json. Only saved images are
+// filtered, not the window, recorded frames or checkpoints.
+pub fn post_processing() -> PostProcessing {
+    PostProcessing::default()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PostProcessing {
+    #[serde(default)]
+    pub filters: Vec<ImageFilter>,
+}
+
+// Amounts are in color channels that go from 0 to 1, and sizes are in pixels.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ImageFilter {
+    // Gray noise. The amount is its standard deviation.
+    FilmGrain { amount: f32 },
+    // Darkens the valleys between the paper's fibers. The scale is how big the
+    // biggest bumps are.
+    PaperTexture { amount: f32, scale: f32 },
+    // Darkens towards the corners, starting at the radius. A radius of 1 is the
+    // distance from the center to a corner.
+    Vignette { amount: f32, radius: f32 },
+    // Pulls the red and blue channels apart towards the edges, like a cheap lens.
+    // The offset is how far apart they are in the corners.
+    ChromaticAberration { offset: f32 },
+    // The radius is the standard deviation.
+    GaussianBlur { radius: f32 },
+    // Cuts each channel down to a few levels, using the method to hide the banding.
+    // Two levels gives a halftone.
+    Dither { method: DitherMethod, levels: usize },
+    // Swaps every pixel for the closest color in the palette.
+    QuantizeToPalette { palette: Vec<ColorConfig> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DitherMethod {
+    // Pushes each pixel's error onto its neighbors. Looks organic, but can form worms.
+    FloydSteinberg,
+    // Compares against a repeating 8x8 Bayer matrix, which gives a regular crosshatch.
+    Ordered,
+    // Compares against a tile of blue noise, which has no visible pattern.
+    BlueNoise,
+}
+
+#[derive(Debug)]
+pub enum PostProcessingError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownFormat(String),
+    InvalidPalette(ColorConfigError),
+}
+
+impl fmt::Display for PostProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcessingError::Io(error) => {
+                write!(f, "Couldn't read the post-processing filters: {error}")
+            }
+            PostProcessingError::Json(error) => {
+                write!(f, "Couldn't parse the post-processing filters: {error}")
+            }
+            PostProcessingError::Toml(error) => {
+                write!(f, "Couldn't parse the post-processing filters: {error}")
+            }
+            PostProcessingError::UnknownFormat(path) => {
+                write!(f, "{path} should end in .json or .toml.")
+            }
+            PostProcessingError::InvalidPalette(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for PostProcessingError {}
+
+impl From<std::io::Error> for PostProcessingError {
+    fn from(error: std::io::Error) -> Self {
+        PostProcessingError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for PostProcessingError {
+    fn from(error: serde_json::Error) -> Self {
+        PostProcessingError::Json(error)
+    }
+}
+
+impl From<toml::de::Error> for PostProcessingError {
+    fn from(error: toml::de::Error) -> Self {
+        PostProcessingError::Toml(error)
+    }
+}
+
+impl From<ColorConfigError> for PostProcessingError {
+    fn from(error: ColorConfigError) -> Self {
+        PostProcessingError::InvalidPalette(error)
+    }
+}
+
+impl PostProcessing {
+    // --post-process <path to a .json or .toml file>
+    pub fn from_command_line() -> Option<PostProcessing> {
+        let path = command_line::value_of("--post-process")?;
+        let post_processing =
+            PostProcessing::load(Path::new(&path)).unwrap_or_else(|error| panic!("{error}"));
+
+        Some(post_processing)
+    }
+
+    pub fn load(path: &Path) -> Result<PostProcessing, PostProcessingError> {
+        let text = fs::read_to_string(path)?;
+
+        let post_processing: PostProcessing =
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => serde_json::from_str(&text)?,
+                Some("toml") => toml::from_str(&text)?,
+                _ => {
+                    return Err(PostProcessingError::UnknownFormat(
+                        path.display().to_string(),
+                    ))
+                }
+            };
+
+        // Better to find out about a typo now than after the render.
+        for filter in &post_processing.filters {
+            if let ImageFilter::QuantizeToPalette { palette } = filter {
+                for color in palette {
+                    color.to_hsl()?;
+                }
+            }
+        }
+
+        Ok(post_processing)
+    }
+
+    pub fn film_grain(self, amount: f32) -> Self {
+        self.filter(ImageFilter::FilmGrain { amount })
+    }
+
+    pub fn paper_texture(self, amount: f32, scale: f32) -> Self {
+        self.filter(ImageFilter::PaperTexture { amount, scale })
+    }
+
+    pub fn vignette(self, amount: f32, radius: f32) -> Self {
+        self.filter(ImageFilter::Vignette { amount, radius })
+    }
+
+    pub fn chromatic_aberration(self, offset: f32) -> Self {
+        self.filter(ImageFilter::ChromaticAberration { offset })
+    }
+
+    pub fn gaussian_blur(self, radius: f32) -> Self {
+        self.filter(ImageFilter::GaussianBlur { radius })
+    }
+
+    pub fn dither(self, method: DitherMethod, levels: usize) -> Self {
+        self.filter(ImageFilter::Dither { method, levels })
+    }
+
+    pub fn quantize_to_palette(self, palette: Vec<Hsl>) -> Self {
+        let palette = palette
+            .into_iter()
+            .map(|color| ColorConfig::Hsl {
+                hue: color.normalized_hue(),
+                saturation: color.saturation,
+                lightness: color.lightness,
+            })
+            .collect();
+
+        self.filter(ImageFilter::QuantizeToPalette { palette })
+    }
+
+    pub fn filter(mut self, filter: ImageFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    // The seed keeps the grain, the paper and the blue noise the same every time we
+    // save an image from the same snapshot.
+    pub fn apply(&self, image: &RgbaImage, seed: u64) -> RgbaImage {
+        let rand = Rand::from_seed(seed);
+        let mut pixels = Pixels::from_image(image);
+
+        for filter in &self.filters {
+            match filter {
+                ImageFilter::FilmGrain { amount } => film_grain(&mut pixels, *amount, &rand),
+                ImageFilter::PaperTexture { amount, scale } => {
+                    paper_texture(&mut pixels, *amount, *scale, &rand)
+                }
+                ImageFilter::Vignette { amount, radius } => vignette(&mut pixels, *amount, *radius),
+                ImageFilter::ChromaticAberration { offset } => {
+                    chromatic_aberration(&mut pixels, *offset)
+                }
+                ImageFilter::GaussianBlur { radius } => gaussian_blur(&mut pixels, *radius),
+                ImageFilter::Dither { method, levels } => {
+                    dither(&mut pixels, *method, *levels, &rand)
+                }
+                ImageFilter::QuantizeToPalette { palette } => {
+                    let palette: Vec<Hsl> = palette
+                        .iter()
+                        .map(|color| color.to_hsl().unwrap_or_else(|error| panic!("{error}")))
+                        .collect();
+
+                    quantize_to_palette(&mut pixels, &palette);
+                }
+            }
+        }
+
+        pixels.to_image()
+    }
+}
+
+// Colors go from 0 to 1 while we work, so filters can go out of range without
+// clipping until the end. Alpha is left alone.
+struct Pixels {
+    width: usize,
+    height: usize,
+    colors: Vec<[f32; 3]>,
+    alphas: Vec<u8>,
+}
+
+impl Pixels {
+    fn from_image(image: &RgbaImage) -> Pixels {
+        let colors = image
+            .pixels()
+            .map(|Rgba([red, green, blue, _])| {
+                [
+                    *red as f32 / 255.0,
+                    *green as f32 / 255.0,
+                    *blue as f32 / 255.0,
+                ]
+            })
+            .collect();
+
+        Pixels {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            colors,
+            alphas: image.pixels().map(|Rgba([.., alpha])| *alpha).collect(),
+        }
+    }
+
+    fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let index = self.index(x as usize, y as usize);
+            let [red, green, blue] =
+                self.colors[index].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+            Rgba([red, green, blue, self.alphas[index]])
+        })
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    fn center(&self) -> Vec2 {
+        vec2(self.width as f32, self.height as f32) / 2.0
+    }
+
+    // Bilinear, and clamped to the edges.
+    fn sample(&self, point: Vec2, channel: usize) -> f32 {
+        let x = point.x.clamp(0.0, (self.width - 1) as f32);
+        let y = point.y.clamp(0.0, (self.height - 1) as f32);
+
+        let left = x.floor() as usize;
+        let top = y.floor() as usize;
+        let right = (left + 1).min(self.width - 1);
+        let bottom = (top + 1).min(self.height - 1);
+
+        let value_at = |x, y| self.colors[self.index(x, y)][channel];
+        let top_value =
+            (x - left as f32).linear_interpolate(value_at(left, top), value_at(right, top));
+        let bottom_value =
+            (x - left as f32).linear_interpolate(value_at(left, bottom), value_at(right, bottom));
+
+        (y - top as f32).linear_interpolate(top_value, bottom_value)
+    }
+}
+
+fn film_grain(pixels: &mut Pixels, amount: f32, rand: &Rand) {
+    for color in &mut pixels.colors {
+        let grain = rand.standard_gaussian() * amount;
+        *color = color.map(|channel| channel + grain);
+    }
+}
+
+// Two octaves of noise, one for the bumps and one for the fibers.
+fn paper_texture(pixels: &mut Pixels, amount: f32, scale: f32, rand: &Rand) {
+    let scale = scale.max(1.0);
+
+    for y in 0..pixels.height {
+        for x in 0..pixels.width {
+            let (x_f32, y_f32) = (x as f32 / scale, y as f32 / scale);
+            let bumps = rand.super_simplex_x_y(x_f32, y_f32);
+            let fibers = rand.super_simplex_x_y(x_f32 * 8.0 + 100.0, y_f32 * 2.0 + 100.0);
+
+            // The noise overshoots 0 to 1 a little, and the amount can be more than 1.
+            // Either way, paper should only ever darken the image.
+            let darkness = (amount * (0.6 * bumps + 0.4 * fibers)).clamp(0.0, 1.0);
+            let index = pixels.index(x, y);
+            pixels.colors[index] = pixels.colors[index].map(|channel| channel * (1.0 - darkness));
+        }
+    }
+}
+
+fn vignette(pixels: &mut Pixels, amount: f32, radius: f32) {
+    let center = pixels.center();
+    let half_diagonal = center.length();
+    let radius = radius.clamp(0.0, 0.99);
+
+    for y in 0..pixels.height {
+        for x in 0..pixels.width {
+            let distance = (vec2(x as f32, y as f32) - center).length() / half_diagonal;
+
+            // Smoothstep from the radius out to the corners.
+            let progress = ((distance - radius) / (1.0 - radius)).clamp(0.0, 1.0);
+            let darkness = amount * progress * progress * (3.0 - 2.0 * progress);
+
+            let index = pixels.index(x, y);
+            pixels.colors[index] = pixels.colors[index].map(|channel| channel * (1.0 - darkness));
+        }
+    }
+}
+
+fn chromatic_aberration(pixels: &mut Pixels, offset: f32) {
+    let center = pixels.center();
+    let half_diagonal = center.length();
+
+    let colors = (0..pixels.height)
+        .flat_map(|y| (0..pixels.width).map(move |x| vec2(x as f32, y as f32)))
+        .map(|point| {
+            // Half the offset each way, so they're the whole offset apart in the corners.
+            let shift = (point - center) / half_diagonal * offset / 2.0;
+            let green = pixels.colors[pixels.index(point.x as usize, point.y as usize)][1];
+
+            [
+                pixels.sample(point + shift, 0),
+                green,
+                pixels.sample(point - shift, 2),
+            ]
+        })
+        .collect();
+
+    pixels.colors = colors;
+}
+
+// Blurs the rows, then the columns, which is the same as a 2D gaussian but faster.
+fn gaussian_blur(pixels: &mut Pixels, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+
+    let kernel = gaussian_kernel(radius);
+    let reach = (kernel.len() / 2) as isize;
+    let (width, height) = (pixels.width as isize, pixels.height as isize);
+
+    let blur = |colors: &Vec<[f32; 3]>, step: (isize, isize)| -> Vec<[f32; 3]> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mut sum = [0.0; 3];
+
+                for (kernel_index, weight) in kernel.iter().enumerate() {
+                    let distance = kernel_index as isize - reach;
+                    let sample_x = (x + distance * step.0).clamp(0, width - 1);
+                    let sample_y = (y + distance * step.1).clamp(0, height - 1);
+                    let color = colors[(sample_y * width + sample_x) as usize];
+
+                    for channel in 0..3 {
+                        sum[channel] += color[channel] * weight;
+                    }
+                }
+
+                sum
+            })
+            .collect()
+    };
+
+    let blurred_rows = blur(&pixels.colors, (1, 0));
+    pixels.colors = blur(&blurred_rows, (0, 1));
+}
+
+// Reaches out to three standard deviations, and adds up to 1.
+fn gaussian_kernel(standard_deviation: f32) -> Vec<f32> {
+    let reach = (standard_deviation * 3.0).ceil() as isize;
+
+    let weights: Vec<f32> = (-reach..=reach)
+        .map(|distance| {
+            let distance = distance as f32;
+            (-(distance * distance) / (2.0 * standard_deviation * standard_deviation)).exp()
+        })
+        .collect();
+
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+fn dither(pixels: &mut Pixels, method: DitherMethod, levels: usize, rand: &Rand) {
+    let steps = (levels.max(2) - 1) as f32;
+
+    match method {
+        DitherMethod::FloydSteinberg => floyd_steinberg(pixels, steps),
+        DitherMethod::Ordered => threshold(pixels, steps, bayer_threshold),
+        DitherMethod::BlueNoise => {
+            let blue_noise = blue_noise_tile(rand);
+            threshold(pixels, steps, |x, y| {
+                blue_noise[(y % blue_noise_size) * blue_noise_size + x % blue_noise_size]
+            })
+        }
+    }
+}
+
+fn floyd_steinberg(pixels: &mut Pixels, steps: f32) {
+    let (width, height) = (pixels.width, pixels.height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = pixels.index(x, y);
+            let old_color = pixels.colors[index].map(|channel| channel.clamp(0.0, 1.0));
+            let new_color = old_color.map(|channel| (channel * steps).round() / steps);
+            pixels.colors[index] = new_color;
+
+            let neighbors = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+            for (x_offset, y_offset, sixteenths) in neighbors {
+                let neighbor_x = x as isize + x_offset;
+                let neighbor_y = y + y_offset;
+
+                if neighbor_x < 0 || neighbor_x >= width as isize || neighbor_y >= height {
+                    continue;
+                }
+
+                let neighbor_index = pixels.index(neighbor_x as usize, neighbor_y);
+                for channel in 0..3 {
+                    let error = old_color[channel] - new_color[channel];
+                    pixels.colors[neighbor_index][channel] += error * sixteenths / 16.0;
+                }
+            }
+        }
+    }
+}
+
+// The threshold at each pixel goes from 0 to 1, and decides whether to round up.
+fn threshold(pixels: &mut Pixels, steps: f32, threshold_at: impl Fn(usize, usize) -> f32) {
+    for y in 0..pixels.height {
+        for x in 0..pixels.width {
+            let threshold = threshold_at(x, y);
+            let index = pixels.index(x, y);
+
+            pixels.colors[index] = pixels.colors[index].map(|channel| {
+                ((channel.clamp(0.0, 1.0) * steps + threshold).floor() / steps).min(1.0)
+            });
+        }
+    }
+}
+
+// Interleaves the bits of x xor y and y, lowest bits first.
+fn bayer_threshold(x: usize, y: usize) -> f32 {
+    let (x, y) = (x % 8, y % 8);
+    let x_xor_y = x ^ y;
+
+    let mut value = 0;
+    for bit in 0..3 {
+        value = (value << 2) | (((x_xor_y >> bit) & 1) << 1) | ((y >> bit) & 1);
+    }
+
+    (value as f32 + 0.5) / 64.0
+}
+
+const blue_noise_size: usize = 64;
+
+// White noise with its low frequencies blurred away, then ranked so the thresholds
+// are spread evenly. It isn't as even as void and cluster, but it's quick and has
+// no visible structure.
+fn blue_noise_tile(rand: &Rand) -> Vec<f32> {
+    let num_pixels = blue_noise_size * blue_noise_size;
+    let white_noise: Vec<f32> = (0..num_pixels).map(|_| rand.zero_to_one()).collect();
+
+    let kernel = gaussian_kernel(1.5);
+    let reach = (kernel.len() / 2) as isize;
+    let size = blue_noise_size as isize;
+
+    // Wraps around, so the tile repeats without seams.
+    let blur = |values: &Vec<f32>, step: (isize, isize)| -> Vec<f32> {
+        (0..size)
+            .flat_map(|y| (0..size).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(kernel_index, weight)| {
+                        let distance = kernel_index as isize - reach;
+                        let sample_x = (x + distance * step.0).rem_euclid(size);
+                        let sample_y = (y + distance * step.1).rem_euclid(size);
+                        values[(sample_y * size + sample_x) as usize] * weight
+                    })
+                    .sum()
+            })
+            .collect()
+    };
+
+    let low_frequencies = blur(&blur(&white_noise, (1, 0)), (0, 1));
+    let high_frequencies: Vec<f32> = white_noise
+        .iter()
+        .zip(&low_frequencies)
+        .map(|(value, low)| value - low)
+        .collect();
+
+    let mut ranked: Vec<usize> = (0..num_pixels).collect();
+    ranked.sort_by(|a, b| high_frequencies[*a].total_cmp(&high_frequencies[*b]));
+
+    let mut thresholds = vec![0.0; num_pixels];
+    for (rank, index) in ranked.into_iter().enumerate() {
+        thresholds[index] = (rank as f32 + 0.5) / num_pixels as f32;
+    }
+
+    thresholds
+}
+
+fn quantize_to_palette(pixels: &mut Pixels, palette: &[Hsl]) {
+    if palette.is_empty() {
+        return;
+    }
+
+    let palette: Vec<[f32; 3]> = palette
+        .iter()
+        .map(|color| {
+            let (red, green, blue) = Srgb::from_hsl(*color).into_components();
+            [red, green, blue]
+        })
+        .collect();
+
+    for color in &mut pixels.colors {
+        *color = *palette
+            .iter()
+            .min_by(|a, b| color_distance(color, a).total_cmp(&color_distance(color, b)))
+            .unwrap();
+    }
+}
+
+// The "redmean" approximation, which weighs the channels roughly like our eyes do
+// without converting to a perceptual color space.
+fn color_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let mean_red = (a[0] + b[0]) / 2.0;
+    let [red, green, blue] = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+
+    (2.0 + mean_red) * red * red + 4.0 * green * green + (3.0 - mean_red) * blue * blue
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use nannou::image::{Rgba, RgbaImage};
+
+    fn gray_image() -> RgbaImage {
+        RgbaImage::from_pixel(33, 33, Rgba([128, 128, 128, 255]))
+    }
+
+    #[test]
+    fn dithering_to_two_levels_keeps_the_average_brightness() {
+        let gray = RgbaImage::from_pixel(32, 32, Rgba([128, 128, 128, 255]));
+
+        for method in [
+            DitherMethod::FloydSteinberg,
+            DitherMethod::Ordered,
+            DitherMethod::BlueNoise,
+        ] {
+            let dithered = post_processing().dither(method, 2).apply(&gray, 42);
+
+            let channels: Vec<u8> = dithered
+                .pixels()
+                .flat_map(|pixel| pixel.0[..3].to_vec())
+                .collect();
+            assert!(channels
+                .iter()
+                .all(|channel| *channel == 0 || *channel == 255));
+
+            let average = channels.iter().map(|channel| *channel as f32).sum::<f32>()
+                / channels.len() as f32
+                / 255.0;
+            assert!(
+                (average - 0.5).abs() < 0.05,
+                "{method:?} averaged {average}."
+            );
+        }
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners_but_not_the_center() {
+        let vignetted = post_processing()
+            .vignette(0.5, 0.5)
+            .apply(&gray_image(), 42);
+
+        assert_eq!(vignetted.get_pixel(16, 16), &Rgba([128, 128, 128, 255]));
+        for (x, y) in [(0, 0), (32, 0), (0, 32), (32, 32)] {
+            assert!(vignetted.get_pixel(x, y).0[..3]
+                .iter()
+                .all(|channel| *channel < 100));
+        }
+    }
+
+    #[test]
+    fn quantizing_only_leaves_colors_from_the_palette() {
+        let hexes = ["#000000", "#ffffff", "#fca297", "#2a6f97"];
+        let palette: Vec<[u8; 3]> = hexes
+            .iter()
+            .map(|hex| {
+                let channel =
+                    |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap();
+                [channel(1), channel(3), channel(5)]
+            })
+            .collect();
+
+        let gradient = RgbaImage::from_fn(32, 32, |x, y| {
+            Rgba([(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8, 255])
+        });
+        let quantized = post_processing()
+            .filter(ImageFilter::QuantizeToPalette {
+                palette: hexes
+                    .iter()
+                    .map(|hex| ColorConfig::Hex(hex.to_string()))
+                    .collect(),
+            })
+            .apply(&gradient, 42);
+
+        for pixel in quantized.pixels() {
+            let [red, green, blue, _] = pixel.0;
+            assert!(palette.contains(&[red, green, blue]), "{pixel:?}");
+        }
+    }
+
+    #[test]
+    fn grain_and_paper_are_the_same_for_the_same_seed() {
+        for filters in [
+            post_processing().film_grain(0.05),
+            post_processing().paper_texture(0.1, 8.0),
+        ] {
+            let image = filters.apply(&gray_image(), 42);
+
+            assert_eq!(image, filters.apply(&gray_image(), 42));
+            assert_ne!(image, filters.apply(&gray_image(), 43));
+        }
+    }
+
+    #[test]
+    fn paper_texture_only_darkens() {
+        let papered = post_processing()
+            .paper_texture(2.0, 8.0)
+            .apply(&gray_image(), 42);
+
+        assert!(papered
+            .pixels()
+            .all(|pixel| pixel.0[..3].iter().all(|channel| *channel <= 128)));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::snapshot::rand::Rand;
+use std::ops::{Range, RangeInclusive};
+
+// With a single Rand, generating one extra number early on shifts every number that
+// comes after it, which can ruin a composition we liked. Streams avoid that. Each
+// value is derived from the seed, the stream's name and an index, so it stays the
+// same no matter what else gets generated, or in what order.
+//
+//   let petal_rand = rand.stream("petals").at(petal_index);
+//   let jitter = rand.stream("jitter").zero_to_one(point_index);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandStream {
+    key: u64,
+}
+
+impl Rand {
+    pub fn stream(&self, name: &str) -> RandStream {
+        RandStream {
+            key: mix(self.seed() ^ hash_name(name)),
+        }
+    }
+}
+
+impl RandStream {
+    // A stream inside this one, e.g. rand.stream("flowers").stream("petals").
+    pub fn stream(&self, name: &str) -> RandStream {
+        RandStream {
+            key: mix(self.key ^ hash_name(name)),
+        }
+    }
+
+    // A whole Rand for one element, e.g. one petal, with all of Rand's methods.
+    pub fn at(&self, index: usize) -> Rand {
+        Rand::from_seed(self.u64(index))
+    }
+
+    pub fn u64(&self, index: usize) -> u64 {
+        mix(self
+            .key
+            .wrapping_add((index as u64).wrapping_mul(golden_gamma)))
+    }
+
+    // 0 inclusive to 1 exclusive.
+    pub fn zero_to_one(&self, index: usize) -> f32 {
+        unit_f32(self.u64(index) >> 40)
+    }
+
+    pub fn range_f32(&self, index: usize, range: &RangeInclusive<f32>) -> f32 {
+        range.start() + self.zero_to_one(index) * (range.end() - range.start())
+    }
+
+    pub fn range(&self, index: usize, range: Range<usize>) -> usize {
+        let len = range.end.saturating_sub(range.start) as u64;
+        if len == 0 {
+            panic!("Can't pick a number from an empty range.");
+        }
+
+        // Multiplying instead of taking the remainder avoids favoring small numbers.
+        // The top 32 bits are plenty for most ranges, and keep the numbers the same
+        // as they've always been. Bigger ranges need all 64, and 128 bits to multiply.
+        let value = self.u64(index);
+        if len <= 1 << 32 {
+            range.start + (((value >> 32) * len) >> 32) as usize
+        } else {
+            range.start + ((value as u128 * len as u128) >> 64) as usize
+        }
+    }
+
+    pub fn flip_coin(&self, index: usize, probability: f32) -> bool {
+        self.zero_to_one(index) < probability
+    }
+
+    pub fn element<'v, T>(&self, index: usize, elements: &'v [T]) -> &'v T {
+        &elements[self.range(index, 0..elements.len())]
+    }
+
+    // Each coordinate is 0 inclusive to 1 exclusive.
+    pub fn xy(&self, index: usize) -> nannou::geom::Point2 {
+        let value = self.u64(index);
+        let x = unit_f32(value >> 40);
+        let y = unit_f32((value >> 16) & 0xff_ffff);
+
+        nannou::geom::pt2(x, y)
+    }
+}
+
+const golden_gamma: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// Turns the lowest 24 bits into a number from 0 inclusive to 1 exclusive. An f32
+// can represent every one of them exactly.
+fn unit_f32(bits: u64) -> f32 {
+    bits as f32 / (1u64 << 24) as f32
+}
+
+// The SplitMix64 finalizer. Nearby inputs give unrelated outputs.
+pub(super) fn mix(value: u64) -> u64 {
+    let mut value = value;
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+// FNV-1a. Unlike std's hashers, it's guaranteed to give the same answer on every
+// machine and every version of Rust, so old snapshots keep their look.
+pub(super) fn hash_name(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn values_dont_depend_on_what_else_was_generated() {
+        let rand = Rand::from_seed(7);
+        let before = rand.stream("petals").zero_to_one(3);
+
+        for _ in 0..10 {
+            rand.zero_to_one();
+        }
+        rand.stream("leaves").zero_to_one(3);
+
+        assert_eq!(rand.stream("petals").zero_to_one(3), before);
+        assert_eq!(Rand::from_seed(7).stream("petals").zero_to_one(3), before);
+    }
+
+    #[test]
+    fn names_and_indices_give_different_values() {
+        let rand = Rand::from_seed(7);
+        let petals = rand.stream("petals");
+
+        assert_ne!(petals.u64(0), petals.u64(1));
+        assert_ne!(petals.u64(0), rand.stream("leaves").u64(0));
+        assert_ne!(petals.u64(0), Rand::from_seed(8).stream("petals").u64(0));
+
+        for index in 0..1000 {
+            let value = petals.zero_to_one(index);
+            assert!((0.0..1.0).contains(&value));
+            assert!(petals.range(index, 3..7) < 7);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::prelude::*;
+use crate::snapshot::rand::Rand;
+use geo::Contains;
+use std::ops::RangeInclusive;
+
+// Where to put sample points: a rect, or the inside of a polygon or shell.
+pub struct SamplingArea {
+    bounds: Rect,
+    polygon: Option<geo::Polygon<f32>>,
+}
+
+impl SamplingArea {
+    pub fn unit_square() -> SamplingArea {
+        SamplingArea::rect(Rect::from_corners(pt2(0.0, 0.0), pt2(1.0, 1.0)))
+    }
+
+    pub fn rect(rect: Rect) -> SamplingArea {
+        SamplingArea {
+            bounds: rect,
+            polygon: None,
+        }
+    }
+
+    pub fn polygon(path: &Path2) -> SamplingArea {
+        SamplingArea {
+            bounds: path.bounding_box(),
+            polygon: Some(path.as_geo_polygon()),
+        }
+    }
+
+    pub fn shell(shell: &Shell) -> SamplingArea {
+        SamplingArea::polygon(&shell.clone().into_path())
+    }
+
+    pub fn contains(&self, point: &Point2) -> bool {
+        let is_in_bounds = point.x.is_between(self.bounds.left(), self.bounds.right())
+            && point.y.is_between(self.bounds.bottom(), self.bounds.top());
+
+        is_in_bounds
+            && match &self.polygon {
+                Some(polygon) => polygon.contains(&geo::Point::new(point.x, point.y)),
+                None => true,
+            }
+    }
+
+    // Maps a point in the unit square onto the area's bounding box.
+    fn denormalize(&self, point: &Point2) -> Point2 {
+        pt2(
+            point.x.denormalize(self.bounds.left(), self.bounds.right()),
+            point.y.denormalize(self.bounds.bottom(), self.bounds.top()),
+        )
+    }
+}
+
+impl Rand {
+    // Blue noise: points that are never closer than min_distance to each other, but
+    // otherwise spread out randomly.
+    pub fn poisson_points(&self, area: &SamplingArea, min_distance: f32) -> Vec<Point2> {
+        self.variable_poisson_points(area, &(min_distance..=min_distance), |_| 1.0)
+    }
+
+    // Like poisson_points, but the distance between points varies across the area.
+    // Where the density is 1 they're the start of distance_range apart, and where
+    // it's 0 they're the end of it apart.
+    pub fn variable_poisson_points<DensityAt>(
+        &self,
+        area: &SamplingArea,
+        distance_range: &RangeInclusive<f32>,
+        density_at: DensityAt,
+    ) -> Vec<Point2>
+    where
+        DensityAt: Fn(&Point2) -> NormalizedF32,
+    {
+        let min_distance_at = |point: &Point2| {
+            density_at(point)
+                .clamp(0.0, 1.0)
+                .denormalize(*distance_range.end(), *distance_range.start())
+        };
+
+        let mut grid = PoissonGrid::new(&area.bounds, *distance_range.start());
+        let mut active_indices: Vec<usize> = vec![];
+
+        // Bridson's algorithm grows outwards from one point. A polygon can have parts
+        // that aren't connected, so we throw a few darts to start from as well.
+        for _ in 0..num_poisson_darts {
+            let dart = area.denormalize(&self.xy());
+            if area.contains(&dart) && grid.has_room_for(&dart, min_distance_at(&dart)) {
+                active_indices.push(grid.insert(dart));
+            }
+
+            while let Some(active_index) = active_indices.last().copied() {
+                let active_point = grid.points[active_index];
+                let distance = min_distance_at(&active_point);
+
+                let new_point = (0..num_poisson_candidates)
+                    .map(|_| {
+                        let angle = self.zero_to_one();
+                        let candidate_distance = self.range_f32(&(distance..=distance * 2.0));
+                        active_point + Vec2::from_angle(angle) * candidate_distance
+                    })
+                    .find(|candidate| {
+                        area.contains(candidate)
+                            && grid.has_room_for(candidate, min_distance_at(candidate))
+                    });
+
+                match new_point {
+                    Some(new_point) => active_indices.push(grid.insert(new_point)),
+                    None => {
+                        active_indices.pop();
+                    }
+                }
+            }
+        }
+
+        grid.points
+    }
+
+    // Mitchell's best candidate algorithm. Each new point is the one out of
+    // num_candidates random candidates that's furthest from the points so far.
+    // Not as even as poisson_points, but we get exactly num_points.
+    pub fn best_candidate_points(
+        &self,
+        area: &SamplingArea,
+        num_points: usize,
+        num_candidates: usize,
+    ) -> Vec<Point2> {
+        let mut points: Vec<Point2> = Vec::with_capacity(num_points);
+
+        while points.len() < num_points {
+            let best_candidate = (0..num_candidates.max(1))
+                .map(|_| self.random_point_in_area(area))
+                .map(|candidate| {
+                    let distance_to_nearest = points
+                        .iter()
+                        .map(|point| point.distance_squared(candidate))
+                        .fold(f32::MAX, f32::min);
+                    (candidate, distance_to_nearest)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(candidate, _)| candidate)
+                .unwrap();
+
+            points.push(best_candidate);
+        }
+
+        points
+    }
+
+    // One randomly placed point in each cell of a grid over the area's bounding box.
+    // Cells whose point lands outside the area are left empty.
+    pub fn stratified_points(
+        &self,
+        area: &SamplingArea,
+        num_columns: usize,
+        num_rows: usize,
+    ) -> Vec<Point2> {
+        (0..num_rows)
+            .flat_map(|row| (0..num_columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let x = (column as f32 + self.zero_to_one()) / num_columns as f32;
+                let y = (row as f32 + self.zero_to_one()) / num_rows as f32;
+                area.denormalize(&pt2(x, y))
+            })
+            .filter(|point| area.contains(point))
+            .collect()
+    }
+
+    // Low discrepancy sequences fill space evenly without looking like a grid. Each
+    // one is shifted by a random offset, so different seeds give different points.
+    pub fn halton_points(&self, area: &SamplingArea, num_points: usize) -> Vec<Point2> {
+        let offset = self.xy();
+        sequence_points(area, num_points, |index| {
+            let point = pt2(radical_inverse(index, 2), radical_inverse(index, 3));
+            wrap_unit(point + offset)
+        })
+    }
+
+    pub fn sobol_points(&self, area: &SamplingArea, num_points: usize) -> Vec<Point2> {
+        // Flipping the same random bits in every point keeps the sequence's
+        // structure. Adding an offset, like we do for the others, would break it.
+        let scramble = [self.sobol_scramble(), self.sobol_scramble()];
+
+        sequence_points(area, num_points, |index| {
+            let [x, y] = sobol_2d(index as u32);
+            pt2(to_unit(x ^ scramble[0]), to_unit(y ^ scramble[1]))
+        })
+    }
+
+    // Martin Roberts' R2 sequence, based on the plastic number.
+    // http://extremelearning.com.au/unreasonable-effectiveness-of-quasirandom-sequences/
+    pub fn r2_points(&self, area: &SamplingArea, num_points: usize) -> Vec<Point2> {
+        let plastic_number: f64 = 1.324_717_957_244_746;
+        let step = [
+            1.0 / plastic_number,
+            1.0 / (plastic_number * plastic_number),
+        ];
+        let offset = self.xy();
+
+        sequence_points(area, num_points, |index| {
+            let x = (step[0] * index as f64).fract() as f32;
+            let y = (step[1] * index as f64).fract() as f32;
+            wrap_unit(pt2(x, y) + offset)
+        })
+    }
+
+    fn sobol_scramble(&self) -> u32 {
+        let high = self.range(0..=u16::MAX as usize) as u32;
+        let low = self.range(0..=u16::MAX as usize) as u32;
+        (high << 16) | low
+    }
+
+    fn random_point_in_area(&self, area: &SamplingArea) -> Point2 {
+        for _ in 0..max_attempts_per_point {
+            let point = area.denormalize(&self.xy());
+            if area.contains(&point) {
+                return point;
+            }
+        }
+
+        panic!("Couldn't find a point inside the sampling area. Is it empty?");
+    }
+}
+
+// How many candidates to try around each point before giving up on it. 30 is what
+// Bridson suggests.
+const num_poisson_candidates: usize = 30;
+const num_poisson_darts: usize = 30;
+const max_attempts_per_point: usize = 10_000;
+// About 0.00045 apart in the unit square.
+const max_poisson_grid_cells: usize = 10_000_000;
+
+// Points from a sequence in the unit square, mapped onto the area. Ones outside it
+// are skipped, and we keep going until we have num_points.
+fn sequence_points<PointAt>(
+    area: &SamplingArea,
+    num_points: usize,
+    point_at: PointAt,
+) -> Vec<Point2>
+where
+    PointAt: Fn(usize) -> Point2,
+{
+    let points: Vec<Point2> = (0..num_points * max_attempts_per_point)
+        .map(|index| area.denormalize(&point_at(index)))
+        .filter(|point| area.contains(point))
+        .take(num_points)
+        .collect();
+
+    if points.len() < num_points {
+        panic!("Couldn't find enough points inside the sampling area. Is it empty?");
+    }
+
+    points
+}
+
+fn wrap_unit(point: Point2) -> Point2 {
+    pt2(point.x.rem_euclid(1.0), point.y.rem_euclid(1.0))
+}
+
+// The digits of index in the given base, mirrored around the decimal point.
+fn radical_inverse(index: usize, base: usize) -> f32 {
+    let mut index = index;
+    let mut result = 0.0;
+    let mut digit_value = 1.0 / base as f64;
+
+    while index > 0 {
+        result += (index % base) as f64 * digit_value;
+        index /= base;
+        digit_value /= base as f64;
+    }
+
+    result as f32
+}
+
+fn sobol_2d(index: u32) -> [u32; 2] {
+    // The first dimension is the van der Corput sequence. The second uses the
+    // direction numbers from the primitive polynomial x + 1.
+    let mut x = 0;
+    let mut y = 0;
+    let mut direction_number: u32 = 1;
+
+    for bit in 0..32 {
+        if index & (1 << bit) != 0 {
+            x ^= 1 << (31 - bit);
+            y ^= direction_number << (31 - bit);
+        }
+        direction_number ^= direction_number << 1;
+    }
+
+    [x, y]
+}
+
+fn to_unit(bits: u32) -> f32 {
+    // Only keep as many bits as an f32 can represent, so we never round up to 1.
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+// Splits the bounding box into cells small enough to hold at most one point, so we
+// only need to check nearby cells to see if a point has room.
+struct PoissonGrid {
+    bounds: Rect,
+    cell_size: f32,
+    num_columns: usize,
+    num_rows: usize,
+    cells: Vec<Option<usize>>,
+    points: Vec<Point2>,
+}
+
+impl PoissonGrid {
+    fn new(bounds: &Rect, min_distance: f32) -> PoissonGrid {
+        let cell_size = min_distance / 2.0_f32.sqrt();
+        let num_columns = (bounds.w() / cell_size).ceil().max(1.0);
+        let num_rows = (bounds.h() / cell_size).ceil().max(1.0);
+
+        // Otherwise a tiny distance would try to allocate billions of cells, and 0
+        // infinitely many.
+        let is_positive = min_distance > 0.0;
+        if !is_positive || num_columns * num_rows > max_poisson_grid_cells as f32 {
+            panic!(
+                "A min distance of {min_distance} is too small for poisson points in a {} by {} area.",
+                bounds.w(),
+                bounds.h()
+            );
+        }
+
+        let num_columns = num_columns as usize;
+        let num_rows = num_rows as usize;
+
+        PoissonGrid {
+            bounds: *bounds,
+            cell_size,
+            num_columns,
+            num_rows,
+            cells: vec![None; num_columns * num_rows],
+            points: vec![],
+        }
+    }
+
+    fn cell_of(&self, point: &Point2) -> [usize; 2] {
+        let column = ((point.x - self.bounds.left()) / self.cell_size) as usize;
+        let row = ((point.y - self.bounds.bottom()) / self.cell_size) as usize;
+
+        [column.min(self.num_columns - 1), row.min(self.num_rows - 1)]
+    }
+
+    fn has_room_for(&self, point: &Point2, min_distance: f32) -> bool {
+        let [column, row] = self.cell_of(point);
+        let reach = (min_distance / self.cell_size).ceil() as usize;
+
+        let columns = column.saturating_sub(reach)..=(column + reach).min(self.num_columns - 1);
+        let rows = row.saturating_sub(reach)..=(row + reach).min(self.num_rows - 1);
+
+        rows.flat_map(|row| columns.clone().map(move |column| (column, row)))
+            .filter_map(|(column, row)| self.cells[row * self.num_columns + column])
+            .all(|index| self.points[index].distance(*point) >= min_distance)
+    }
+
+    fn insert(&mut self, point: Point2) -> usize {
+        let index = self.points.len();
+        let [column, row] = self.cell_of(&point);
+
+        self.cells[row * self.num_columns + column] = Some(index);
+        self.points.push(point);
+
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn poisson_points_stay_inside_and_apart() {
+        let rand = Rand::from_seed(7);
+        let triangle = vec![pt2(0.1, 0.1), pt2(0.9, 0.1), pt2(0.5, 0.9), pt2(0.1, 0.1)];
+        let area = SamplingArea::polygon(&triangle);
+
+        let points = rand.variable_poisson_points(&area, &(0.02..=0.05), |point| point.x);
+        assert!(points.len() > 50);
+
+        for (index, point) in points.iter().enumerate() {
+            assert!(area.contains(point));
+
+            for other in &points[index + 1..] {
+                assert!(point.distance(*other) >= 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn sequences_are_deterministic_and_fill_the_square() {
+        let area = SamplingArea::unit_square();
+
+        for sequence in [Rand::halton_points, Rand::sobol_points, Rand::r2_points] {
+            let points = sequence(&Rand::from_seed(7), &area, 1024);
+            assert_eq!(points, sequence(&Rand::from_seed(7), &area, 1024));
+
+            // Every cell of an 8 by 8 grid should get at least one point.
+            let mut is_filled = [false; 64];
+            for point in points {
+                is_filled[(point.y * 8.0) as usize * 8 + (point.x * 8.0) as usize] = true;
+            }
+            assert!(is_filled.iter().all(|is_filled| *is_filled));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use super::image::find_image;
+use super::manifest;
+use super::metadata::SnapshotMetadata;
+use crate::library::command_line;
+use crate::prelude::*;
+use nannou::image::{self, imageops, Pixel, Rgba, RgbaImage};
+use nannou::text::{font, rt, Font, Scale};
+use std::fs;
+use std::path::PathBuf;
+
+const tile_width: u32 = 400;
+const padding: u32 = 16;
+const font_size: f32 = 16.0;
+const line_height: u32 = 20;
+const num_label_lines: u32 = 2;
+
+// Which snapshots to put on the contact sheet.
+pub enum Selection {
+    // Everything rendered by one --seeds or --random-seeds run.
+    SeedSweep(String),
+    // Everything created between two dates, inclusive. Dates look like 2023-04-01.
+    DateRange { from: String, to: String },
+    SnapshotNames(Vec<String>),
+}
+
+impl Selection {
+    // --contact-sheet --sweep <name>
+    // --contact-sheet --from 2023-04-01 --to 2023-04-30
+    // --contact-sheet --snapshots first_name,second_name
+    //
+    // With no other flags, we use the most recent seed sweep.
+    pub fn from_command_line() -> Selection {
+        if let Some(seed_sweep) = command_line::value_of("--sweep") {
+            return Selection::SeedSweep(seed_sweep);
+        }
+
+        if let Some(snapshot_names) = command_line::value_of("--snapshots") {
+            let snapshot_names = snapshot_names.split(',').map(String::from).collect();
+            return Selection::SnapshotNames(snapshot_names);
+        }
+
+        let from = command_line::value_of("--from");
+        let to = command_line::value_of("--to");
+        if from.is_some() || to.is_some() {
+            return Selection::DateRange {
+                from: from.unwrap_or_default(),
+                to: to.unwrap_or_else(|| "9999-12-31".to_string()),
+            };
+        }
+
+        let latest_seed_sweep = SnapshotMetadata::load_all()
+            .into_iter()
+            .rev()
+            .find_map(|metadata| metadata.seed_sweep)
+            .expect("Couldn't find any seed sweeps. Try --sweep, --from/--to or --snapshots.");
+
+        Selection::SeedSweep(latest_seed_sweep)
+    }
+
+    fn includes(&self, metadata: &SnapshotMetadata) -> bool {
+        match self {
+            Selection::SeedSweep(seed_sweep) => metadata.seed_sweep.as_ref() == Some(seed_sweep),
+            Selection::DateRange { from, to } => {
+                // RFC 3339 dates sort the same way as strings.
+                let created_on = &metadata.created_at[..10];
+                from.as_str() <= created_on && created_on <= to.as_str()
+            }
+            Selection::SnapshotNames(snapshot_names) => {
+                snapshot_names.contains(&metadata.friendly_name)
+            }
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self {
+            Selection::SeedSweep(seed_sweep) => seed_sweep.clone(),
+            Selection::DateRange { from, to } => format!("{from}_to_{to}"),
+            Selection::SnapshotNames(snapshot_names) => snapshot_names.join("_"),
+        }
+    }
+}
+
+struct Tile {
+    image_name: String,
+    seed: u64,
+    image_path: PathBuf,
+}
+
+// Tiles the selected images into one labelled grid, so we can compare lots of
+// variations at a glance. Saved in contact_sheets/.
+pub fn create(selection: Selection) {
+    let tiles = find_tiles(&selection);
+    if tiles.is_empty() {
+        println!("No images matched, so there's nothing to put on a contact sheet.");
+        return;
+    }
+
+    let contact_sheet = render(&tiles);
+
+    let folder = manifest::folder().join("contact_sheets");
+    fs::create_dir_all(&folder).expect("Couldn't create the contact sheets folder.");
+
+    let path = folder.join(format!("{}.png", selection.file_name()));
+    contact_sheet
+        .save(&path)
+        .expect("Couldn't save the contact sheet.");
+
+    println!(
+        "Saved a contact sheet with {} images to {}.",
+        tiles.len(),
+        path.display()
+    );
+}
+
+fn find_tiles(selection: &Selection) -> Vec<Tile> {
+    SnapshotMetadata::load_all()
+        .into_iter()
+        .filter(|metadata| selection.includes(metadata))
+        .flat_map(|metadata| {
+            let seed = metadata.seed;
+            metadata.images.into_iter().filter_map(move |image_name| {
+                let image_path = find_image(&image_name)?;
+                Some(Tile {
+                    image_name,
+                    seed,
+                    image_path,
+                })
+            })
+        })
+        .collect()
+}
+
+fn render(tiles: &[Tile]) -> RgbaImage {
+    // Assume every image has the same aspect ratio as the first.
+    let (first_width, first_height) =
+        image::image_dimensions(&tiles[0].image_path).expect("Couldn't read an image.");
+    let tile_height = (tile_width as f32 * first_height as f32 / first_width as f32) as u32;
+
+    let label_height = line_height * num_label_lines;
+    let cell_width = tile_width + padding * 2;
+    let cell_height = tile_height + label_height + padding * 2;
+
+    let num_columns = (tiles.len() as f32).sqrt().ceil() as usize;
+    let num_rows = (tiles.len() + num_columns - 1) / num_columns;
+
+    let sheet_width = cell_width * num_columns as u32;
+    let sheet_height = cell_height * num_rows as u32;
+
+    // Pixel coordinates, so y points down and row 0 is at the top.
+    let sheet_rect =
+        Rect::from_corners(pt2(0.0, 0.0), pt2(sheet_width as f32, sheet_height as f32));
+    let grid = RectGrid::new(sheet_rect, num_columns, num_rows);
+    let cells_in_reading_order = grid.rows().into_iter().flatten();
+
+    let background = to_rgba(soft_white());
+    let text_color = to_rgba(soft_black());
+    let font = font::default_notosans();
+
+    let mut contact_sheet = RgbaImage::from_pixel(sheet_width, sheet_height, background);
+
+    for (tile, cell) in tiles.iter().zip(cells_in_reading_order) {
+        let left = cell.rect.left().round() as u32 + padding;
+        let top = cell.rect.bottom().round() as u32 + padding;
+
+        let image = image::open(&tile.image_path)
+            .unwrap_or_else(|error| panic!("Couldn't open {}: {error}", tile.image_path.display()))
+            .to_rgba8();
+        let thumbnail = imageops::resize(
+            &image,
+            tile_width,
+            tile_height,
+            imageops::FilterType::Triangle,
+        );
+        imageops::overlay(&mut contact_sheet, &thumbnail, left, top);
+
+        let first_baseline = top + tile_height + line_height;
+        let labels = [tile.image_name.clone(), format!("seed {}", tile.seed)];
+
+        for (line_index, label) in labels.iter().enumerate() {
+            let baseline = first_baseline + line_height * line_index as u32;
+            draw_text(&mut contact_sheet, &font, label, left, baseline, text_color);
+        }
+    }
+
+    contact_sheet
+}
+
+fn draw_text(
+    image: &mut RgbaImage,
+    font: &Font,
+    text: &str,
+    left: u32,
+    baseline: u32,
+    color: Rgba<u8>,
+) {
+    let glyphs = font.layout(
+        text,
+        Scale::uniform(font_size),
+        rt::point(left as f32, baseline as f32),
+    );
+
+    for glyph in glyphs {
+        let bounding_box = match glyph.pixel_bounding_box() {
+            Some(bounding_box) => bounding_box,
+            None => continue, // Spaces don't have a bounding box.
+        };
+
+        glyph.draw(|x, y, coverage| {
+            let x = bounding_box.min.x + x as i32;
+            let y = bounding_box.min.y + y as i32;
+
+            let is_inside_image =
+                0 <= x && x < image.width() as i32 && 0 <= y && y < image.height() as i32;
+            if !is_inside_image {
+                return;
+            }
+
+            let mut glyph_color = color;
+            glyph_color[3] = (coverage * 255.0) as u8;
+            image.get_pixel_mut(x as u32, y as u32).blend(&glyph_color);
+        });
+    }
+}
+
+fn to_rgba(color: Hsl) -> Rgba<u8> {
+    let srgb8 = color.as_srgb8();
+    Rgba([srgb8.red, srgb8.green, srgb8.blue, 255])
+}
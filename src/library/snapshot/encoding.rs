@@ -0,0 +1,230 @@
+use crate::library::command_line;
+use nannou::image::{self, DynamicImage};
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use tiff::encoder::{colortype, compression::Lzw, TiffEncoder, TiffValue};
+use tiff::tags::Tag;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    LzwTiff,
+    Png,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EncodingOptions {
+    pub format: ImageFormat,
+    pub bit_depth: BitDepth,
+}
+
+impl EncodingOptions {
+    // LZW compressed TIFFs by default. --png saves PNGs instead, and --16-bit saves
+    // 16 bits per channel, which leaves more headroom when editing afterwards.
+    pub fn from_command_line() -> EncodingOptions {
+        let format = if command_line::has_flag("--png") {
+            ImageFormat::Png
+        } else {
+            ImageFormat::LzwTiff
+        };
+
+        let bit_depth = if command_line::has_flag("--16-bit") {
+            BitDepth::Sixteen
+        } else {
+            BitDepth::Eight
+        };
+
+        EncodingOptions { format, bit_depth }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.format {
+            ImageFormat::LzwTiff => "tif",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Io(std::io::Error),
+    Decoding(image::ImageError),
+    Tiff(tiff::TiffError),
+    Png(png::EncodingError),
+    Gif(gif::EncodingError),
+    // GIFs can't be more than 65535 pixels on a side.
+    TooBigForGif { width: u32, height: u32 },
+    CreationTime(chrono::ParseError),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::Io(error) => write!(f, "Couldn't read or write an image file: {error}"),
+            EncodingError::Decoding(error) => write!(f, "Couldn't read an image: {error}"),
+            EncodingError::Tiff(error) => write!(f, "Couldn't encode a TIFF: {error}"),
+            EncodingError::Png(error) => write!(f, "Couldn't encode a PNG: {error}"),
+            EncodingError::Gif(error) => write!(f, "Couldn't encode a GIF: {error}"),
+            EncodingError::TooBigForGif { width, height } => write!(
+                f,
+                "{width}x{height} is too big for a GIF. Try a smaller --width."
+            ),
+            EncodingError::CreationTime(error) => {
+                write!(f, "Couldn't parse a snapshot's creation time: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl From<std::io::Error> for EncodingError {
+    fn from(error: std::io::Error) -> Self {
+        EncodingError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for EncodingError {
+    fn from(error: image::ImageError) -> Self {
+        EncodingError::Decoding(error)
+    }
+}
+
+impl From<tiff::TiffError> for EncodingError {
+    fn from(error: tiff::TiffError) -> Self {
+        EncodingError::Tiff(error)
+    }
+}
+
+impl From<png::EncodingError> for EncodingError {
+    fn from(error: png::EncodingError) -> Self {
+        EncodingError::Png(error)
+    }
+}
+
+impl From<gif::EncodingError> for EncodingError {
+    fn from(error: gif::EncodingError) -> Self {
+        EncodingError::Gif(error)
+    }
+}
+
+impl From<chrono::ParseError> for EncodingError {
+    fn from(error: chrono::ParseError) -> Self {
+        EncodingError::CreationTime(error)
+    }
+}
+
+// Saves the image in the given format. The text gets embedded in the file (as PNG
+// text chunks, or the TIFF image description) so an image can be traced back to
+// its snapshot even after it's been renamed or moved.
+pub fn encode(
+    image: &DynamicImage,
+    path: &Path,
+    options: &EncodingOptions,
+    text: &[(&str, String)],
+) -> Result<(), EncodingError> {
+    match (options.format, options.bit_depth) {
+        (ImageFormat::LzwTiff, BitDepth::Eight) => {
+            let pixels = image.to_rgba8();
+            write_tiff::<colortype::RGBA8>(path, pixels.width(), pixels.height(), &pixels, text)
+        }
+
+        (ImageFormat::LzwTiff, BitDepth::Sixteen) => {
+            let pixels = image.to_rgba16();
+            write_tiff::<colortype::RGBA16>(path, pixels.width(), pixels.height(), &pixels, text)
+        }
+
+        (ImageFormat::Png, BitDepth::Eight) => {
+            let pixels = image.to_rgba8();
+            write_png(
+                path,
+                pixels.width(),
+                pixels.height(),
+                png::BitDepth::Eight,
+                &pixels,
+                text,
+            )
+        }
+
+        (ImageFormat::Png, BitDepth::Sixteen) => {
+            let pixels = image.to_rgba16();
+
+            // PNGs store 16 bit samples big-endian.
+            let bytes: Vec<u8> = pixels
+                .as_raw()
+                .iter()
+                .flat_map(|sample| sample.to_be_bytes())
+                .collect();
+
+            write_png(
+                path,
+                pixels.width(),
+                pixels.height(),
+                png::BitDepth::Sixteen,
+                &bytes,
+                text,
+            )
+        }
+    }
+}
+
+fn write_tiff<ColorType>(
+    path: &Path,
+    width: u32,
+    height: u32,
+    data: &[ColorType::Inner],
+    text: &[(&str, String)],
+) -> Result<(), EncodingError>
+where
+    ColorType: colortype::ColorType,
+    [ColorType::Inner]: TiffValue,
+{
+    let description = text
+        .iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = TiffEncoder::new(file)?;
+    let mut tiff_image =
+        encoder.new_image_with_compression::<ColorType, _>(width, height, Lzw::default())?;
+
+    tiff_image
+        .encoder()
+        .write_tag(Tag::ImageDescription, description.as_str())?;
+    tiff_image.write_data(data)?;
+
+    Ok(())
+}
+
+fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    bit_depth: png::BitDepth,
+    data: &[u8],
+    text: &[(&str, String)],
+) -> Result<(), EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(bit_depth);
+
+    for (key, value) in text {
+        encoder.add_text_chunk(key.to_string(), value.clone())?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+
+    Ok(())
+}
@@ -1,56 +1,117 @@
+use super::encoding::{self, EncodingError, EncodingOptions};
 use crate::snapshot::manifest;
 use crate::snapshot::Snapshot;
+use nannou::image::DynamicImage;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 
-pub fn clean_up(app: &nannou::prelude::App, snapshot: &Snapshot) {
+pub fn clean_up(app: &nannou::prelude::App, snapshot: &Snapshot) -> Result<(), EncodingError> {
     if !snapshot.did_capture_frames {
-        return;
+        return Ok(());
     }
 
-    app.main_window().await_capture_frame_jobs().unwrap();
+    crate::window_dimensions::artwork_window(app)
+        .await_capture_frame_jobs()
+        .unwrap();
 
-    fs::read_dir(images_folder_path())
-        .unwrap()
-        // Get the path for each entry.
-        .map(|entry| entry.unwrap().path().to_str().unwrap().to_owned())
-        // Only keep paths pointing to uncompressed images.
-        .filter(|path| path.contains("uncompressed.tif"))
-        .for_each(|uncompressed_path| clean_up_uncompressed_file(uncompressed_path, snapshot));
+    compress_uncompressed_images(snapshot)
 }
 
-fn clean_up_uncompressed_file(uncompressed_path: String, snapshot: &Snapshot) {
-    let compressed_path = uncompressed_path.replace(" uncompressed.tif", ".tif");
+pub fn compress_uncompressed_images(snapshot: &Snapshot) -> Result<(), EncodingError> {
+    let options = EncodingOptions::from_command_line();
 
-    // convert -compress lzw "image uncompressed.tif" image.tif
-    let output = Command::new("convert")
-        .arg("-compress")
-        .arg("lzw")
-        .arg(&uncompressed_path)
-        .arg(&compressed_path)
-        .output()
-        .unwrap();
+    // Other runs might have left uncompressed images in the folder too, but they're
+    // not ours to compress, and we don't know their post-processing.
+    for image_name in &snapshot.metadata.images {
+        let path = uncompressed_path(image_name.clone());
 
-    if !output.status.success() {
-        let error_message = String::from_utf8(output.stderr).unwrap();
-        panic!("{}", error_message);
+        if path.exists() {
+            let path = path.to_str().unwrap().to_owned();
+            clean_up_uncompressed_file(path, snapshot, &options)?;
+        }
     }
 
-    fs::remove_file(uncompressed_path).unwrap();
+    Ok(())
+}
+
+fn clean_up_uncompressed_file(
+    uncompressed_path: String,
+    snapshot: &Snapshot,
+    options: &EncodingOptions,
+) -> Result<(), EncodingError> {
+    let compressed_path =
+        uncompressed_path.replace(" uncompressed.tif", &format!(".{}", options.extension()));
+    let image_name = snapshot_image_name_from_compressed_path(&compressed_path);
+
+    let image = post_process(
+        nannou::image::open(&uncompressed_path)?,
+        &image_name,
+        snapshot,
+    );
+    encoding::encode(
+        &image,
+        Path::new(&compressed_path),
+        options,
+        &embedded_text(snapshot, &image_name),
+    )?;
+
+    fs::remove_file(uncompressed_path)?;
 
     save_image_placeholder_in_snapshot_directory(compressed_path, snapshot);
+
+    Ok(())
 }
 
-pub fn capture_frame(snapshot: &mut Snapshot, app: &nannou::prelude::App) {
-    let image_path = uncompressed_path(snapshot.image_name());
-    app.main_window().capture_frame(image_path);
+fn post_process(image: DynamicImage, image_name: &str, snapshot: &Snapshot) -> DynamicImage {
+    let post_processing = match snapshot.metadata.image_post_processing.get(image_name) {
+        Some(post_processing) => post_processing,
+        None => return image,
+    };
+
+    DynamicImage::ImageRgba8(post_processing.apply(&image.to_rgba8(), snapshot.seed))
+}
+
+fn embedded_text(snapshot: &Snapshot, image_name: &str) -> Vec<(&'static str, String)> {
+    let mut text = vec![
+        ("Title", image_name.to_string()),
+        ("Snapshot", snapshot.source_code_folder_name.clone()),
+        ("Seed", snapshot.seed.to_string()),
+    ];
+
+    if let Some(git_commit) = &snapshot.metadata.git_commit {
+        text.push(("Git commit", git_commit.clone()));
+    }
+
+    text
+}
+
+pub fn capture_frame(snapshot: &mut Snapshot, app: &nannou::prelude::App) -> String {
+    let image_name = snapshot.image_name();
+    let image_path = uncompressed_path(image_name.clone());
+    crate::window_dimensions::artwork_window(app).capture_frame(image_path);
+
+    image_name
+}
+
+pub fn save_rasterized_frame(
+    snapshot: &mut Snapshot,
+    rgba_image: &nannou::image::RgbaImage,
+) -> String {
+    let image_name = snapshot.image_name();
+    let image_path = uncompressed_path(image_name.clone());
+    rgba_image
+        .save(image_path)
+        .expect("Couldn't save the rasterized frame.");
+
+    image_name
 }
 
 fn snapshot_image_name_from_compressed_path(compressed_path_string: &str) -> String {
-    compressed_path_string
-        .replace(images_folder_path().join("").to_str().unwrap(), "")
-        .replace(".tif", "")
+    Path::new(compressed_path_string)
+        .file_stem()
+        .unwrap()
+        .to_str()
+        .unwrap()
         .trim()
         .to_string()
 }
@@ -83,6 +144,18 @@ pub fn images_folder_path() -> PathBuf {
     manifest::folder().join("images")
 }
 
+// Images might not have been compressed yet, or might have been deleted.
+pub fn find_image(image_name: &str) -> Option<PathBuf> {
+    [
+        format!("{image_name}.tif"),
+        format!("{image_name}.png"),
+        format!("{image_name} uncompressed.tif"),
+    ]
+    .into_iter()
+    .map(|file_name| images_folder_path().join(file_name))
+    .find(|path| path.exists())
+}
+
 fn compressed_name(snapshot_name: String) -> String {
     format!("{}{}", snapshot_name, ".tif")
 }
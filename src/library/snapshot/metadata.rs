@@ -0,0 +1,205 @@
+use super::manifest;
+use crate::artwork::ArtworkConfig;
+use crate::library::parameters::ParameterValue;
+use crate::library::post_processing::PostProcessing;
+use crate::window_dimensions::*;
+use duct::cmd;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const file_name: &str = "snapshot.json";
+
+// Everything we need to find and reproduce a snapshot later. Saved as snapshot.json
+// in the snapshot's folder in examples/.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotMetadata {
+    pub friendly_name: String,
+    pub seed: u64,
+    pub created_at: String,
+    pub git_commit: Option<String>,
+    // Whether there were uncommitted changes when the snapshot was taken.
+    pub git_is_dirty: Option<bool>,
+    pub window_width: f32,
+    pub window_height: f32,
+    pub num_repeats: Option<usize>,
+    pub render_duration_seconds: Option<f64>,
+    pub command_line_flags: Vec<String>,
+    pub images: Vec<String>,
+    // SHA-256 of the raw RGBA pixels of each headlessly rendered image, so replays
+    // can check they produced exactly the same thing. Window captures aren't hashed
+    // because the GPU doesn't give us the same pixels as the CPU rasterizer.
+    #[serde(default)]
+    pub image_hashes: BTreeMap<String, String>,
+    // Set when this snapshot was rendered as one of many seeds with --seeds or
+    // --random-seeds. Every snapshot from the same sweep shares the name.
+    #[serde(default)]
+    pub seed_sweep: Option<String>,
+    // The values of the artwork's declared parameters when each image was captured,
+    // by image name. They can change between captures, e.g. from the parameter panel.
+    #[serde(default)]
+    pub image_parameters: BTreeMap<String, BTreeMap<String, ParameterValue>>,
+    // Set when the artwork came from --artwork-config, so it can be reproduced
+    // without the file.
+    #[serde(default)]
+    pub artwork_config: Option<ArtworkConfig>,
+    // The filters applied to each image before it was saved, by image name. The
+    // hashes in image_hashes are from before the filters.
+    #[serde(default)]
+    pub image_post_processing: BTreeMap<String, PostProcessing>,
+    // Deferred snapshots don't write anything until a frame gets captured.
+    #[serde(skip)]
+    pub is_deferred: bool,
+}
+
+impl SnapshotMetadata {
+    pub fn new(friendly_name: &str, seed: u64) -> SnapshotMetadata {
+        let [window_width, window_height] = get_window_dimensions();
+
+        SnapshotMetadata {
+            friendly_name: friendly_name.to_string(),
+            seed,
+            created_at: chrono::Local::now().to_rfc3339(),
+            git_commit: git_commit(),
+            git_is_dirty: git_is_dirty(),
+            window_width,
+            window_height,
+            num_repeats: None,
+            render_duration_seconds: None,
+            command_line_flags: std::env::args().skip(1).collect(),
+            images: vec![],
+            image_hashes: BTreeMap::new(),
+            seed_sweep: None,
+            image_parameters: BTreeMap::new(),
+            artwork_config: None,
+            image_post_processing: BTreeMap::new(),
+            is_deferred: false,
+        }
+    }
+
+    pub fn load(friendly_name: &str) -> Option<SnapshotMetadata> {
+        SnapshotMetadata::load_from(&path(friendly_name))
+    }
+
+    fn load_from(path: &Path) -> Option<SnapshotMetadata> {
+        let json = fs::read_to_string(path).ok()?;
+        let metadata = serde_json::from_str(&json)
+            .unwrap_or_else(|error| panic!("Couldn't parse {}: {error}", path.display()));
+
+        Some(metadata)
+    }
+
+    // Every snapshot in examples/ that has a snapshot.json, oldest first.
+    pub fn load_all() -> Vec<SnapshotMetadata> {
+        SnapshotMetadata::load_all_in(&examples_folder())
+    }
+
+    // Like load_all, but for a different examples folder, e.g. one in a replay
+    // workspace.
+    pub fn load_all_in(examples_folder: &Path) -> Vec<SnapshotMetadata> {
+        let mut all_metadata: Vec<SnapshotMetadata> = fs::read_dir(examples_folder)
+            .expect("Couldn't find the examples folder.")
+            .filter_map(|entry| {
+                let path = entry.ok()?.path().join(file_name);
+                SnapshotMetadata::load_from(&path)
+            })
+            .collect();
+
+        all_metadata.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        all_metadata
+    }
+
+    pub fn save(&self) {
+        if self.is_deferred {
+            return;
+        }
+
+        let folder = examples_folder().join(&self.friendly_name);
+        fs::create_dir_all(&folder).expect("Couldn't create the snapshot folder.");
+
+        let json =
+            serde_json::to_string_pretty(self).expect("Couldn't serialize the snapshot metadata.");
+        fs::write(path(&self.friendly_name), json).expect("Couldn't save the snapshot metadata.");
+    }
+
+    pub fn record_image(
+        &mut self,
+        image_name: &str,
+        parameters: BTreeMap<String, ParameterValue>,
+        post_processing: &PostProcessing,
+    ) {
+        self.images.push(image_name.to_string());
+        self.insert_image_inputs(image_name, parameters, post_processing);
+        self.save();
+    }
+
+    pub fn record_rasterized_image(
+        &mut self,
+        image_name: &str,
+        image_hash: String,
+        parameters: BTreeMap<String, ParameterValue>,
+        post_processing: &PostProcessing,
+    ) {
+        self.images.push(image_name.to_string());
+        self.image_hashes.insert(image_name.to_string(), image_hash);
+        self.insert_image_inputs(image_name, parameters, post_processing);
+        self.save();
+    }
+
+    fn insert_image_inputs(
+        &mut self,
+        image_name: &str,
+        parameters: BTreeMap<String, ParameterValue>,
+        post_processing: &PostProcessing,
+    ) {
+        if !parameters.is_empty() {
+            self.image_parameters
+                .insert(image_name.to_string(), parameters);
+        }
+
+        if !post_processing.is_empty() {
+            self.image_post_processing
+                .insert(image_name.to_string(), post_processing.clone());
+        }
+    }
+
+    pub fn record_seed_sweep(&mut self, seed_sweep: &str) {
+        self.seed_sweep = Some(seed_sweep.to_string());
+        self.save();
+    }
+
+    pub fn record_render(&mut self, num_repeats: usize, render_duration: Duration) {
+        self.num_repeats = Some(num_repeats);
+        self.render_duration_seconds = Some(render_duration.as_secs_f64());
+        self.save();
+    }
+}
+
+fn examples_folder() -> PathBuf {
+    manifest::folder().join("examples")
+}
+
+fn path(friendly_name: &str) -> PathBuf {
+    examples_folder().join(friendly_name).join(file_name)
+}
+
+// These return None if git isn't installed or we're not in a repository.
+fn git_commit() -> Option<String> {
+    cmd!("git", "rev-parse", "HEAD")
+        .dir(manifest::folder())
+        .stderr_null()
+        .read()
+        .ok()
+}
+
+fn git_is_dirty() -> Option<bool> {
+    cmd!("git", "status", "--porcelain")
+        .dir(manifest::folder())
+        .stderr_null()
+        .read()
+        .ok()
+        .map(|status| !status.trim().is_empty())
+}
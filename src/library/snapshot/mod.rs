@@ -1,51 +1,133 @@
+pub mod contact_sheet;
+pub mod encoding;
 mod friendly_words;
 mod image;
-mod manifest;
+pub use image::images_folder_path;
+pub mod manifest;
+pub mod metadata;
 pub mod rand;
+pub mod replay;
 pub mod seed;
-mod source_code;
+pub mod source_code;
+pub mod source_store;
 pub mod timelapse;
 
+use crate::artwork::ArtworkConfig;
+use crate::library::parameters::ParameterValue;
+use crate::library::post_processing::PostProcessing;
+use metadata::SnapshotMetadata;
 use rand::Rand;
 use seed::Seed;
+use std::collections::BTreeMap;
 
 pub struct Snapshot {
     pub did_capture_frames: bool,
     pub seed: u64,
     pub source_code_folder_name: String,
+    pub metadata: SnapshotMetadata,
+    // The filters for the images captured from now on.
+    pub post_processing: PostProcessing,
     frame_number: u64,
     has_used_source_code_folder_name_as_image_name: bool,
     image_name_randomizer: Rand,
+    // Set until a deferred snapshot gets saved.
+    deferred_seed: Option<Seed>,
 }
 
 impl Snapshot {
-    fn create() -> Snapshot {
-        let seed = Seed::load();
+    fn create(seed: Seed) -> Snapshot {
+        let mut new_snapshot = Snapshot::deferred(seed);
+        new_snapshot.save_if_deferred();
+
+        new_snapshot
+    }
+
+    // Doesn't save anything until the first frame gets captured. When hot reloading,
+    // most versions of the code get thrown away, so they don't need a snapshot.
+    pub fn deferred(seed: Seed) -> Snapshot {
+        let mut new_snapshot = Snapshot::unsaved(seed.value);
+        new_snapshot.metadata.is_deferred = true;
+        new_snapshot.deferred_seed = Some(seed);
+
+        new_snapshot
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        self.deferred_seed.is_some()
+    }
+
+    fn save_if_deferred(&mut self) {
+        let seed = match self.deferred_seed.take() {
+            Some(seed) => seed,
+            None => return,
+        };
+
         seed.save_to_file();
 
+        // An artwork from --artwork-config is all in its config, so that goes in
+        // snapshot.json instead of a copy of the source code. If this is already
+        // saved in the examples folder, no need to save another copy either.
+        if let Some(artwork_config) = ArtworkConfig::from_command_line() {
+            self.metadata.artwork_config = Some(artwork_config);
+        } else if !file!().contains("examples") {
+            source_code::save_current_version(&self.source_code_folder_name);
+        }
+
+        self.metadata.is_deferred = false;
+        self.metadata.save();
+
+        seed.clean_up_file();
+    }
+
+    // Doesn't save the source code or metadata, so nothing ends up in examples/.
+    // Handy for tests.
+    pub fn unsaved(seed: u64) -> Snapshot {
         // Always use the current time as the seed for generating names. If we used
         // the seed saved in the file, this would try to generate the same name as
         // the original file, and error when it tried to overwrite it. Instead, we
         // just generate a new name every time.
         let image_name_randomizer = Rand::from_seed(Seed::get_value_from_current_time());
+        let source_code_folder_name = friendly_name(&image_name_randomizer);
+        let metadata = SnapshotMetadata::new(&source_code_folder_name, seed);
 
-        let new_snapshot = Snapshot {
-            seed: seed.value,
+        Snapshot {
+            seed,
             did_capture_frames: false,
             frame_number: 0,
-            source_code_folder_name: friendly_name(&image_name_randomizer),
+            source_code_folder_name,
+            metadata,
+            post_processing: PostProcessing::default(),
             has_used_source_code_folder_name_as_image_name: false,
             image_name_randomizer,
-        };
+            deferred_seed: None,
+        }
+    }
 
-        // If this is already saved in the examples folder, no need to save another copy.
-        if !file!().contains("examples") {
-            source_code::save_current_version(&new_snapshot.source_code_folder_name);
+    // Carries on with a snapshot from an earlier run, e.g. to resume a render.
+    pub fn resumed(snapshot_name: &str) -> Snapshot {
+        let metadata = SnapshotMetadata::load(snapshot_name)
+            .unwrap_or_else(|| panic!("Couldn't find snapshot.json for {snapshot_name}."));
+
+        if let Some(true) = source_code::has_changed_since(snapshot_name) {
+            println!(
+                "The source code has changed since {snapshot_name} was saved, so the rest of the render might not match."
+            );
         }
 
-        seed.clean_up_file();
+        // The first image already took the snapshot's name.
+        let has_used_source_code_folder_name_as_image_name = !metadata.images.is_empty();
 
-        new_snapshot
+        Snapshot {
+            seed: metadata.seed,
+            did_capture_frames: false,
+            frame_number: 0,
+            source_code_folder_name: snapshot_name.to_string(),
+            metadata,
+            post_processing: PostProcessing::default(),
+            has_used_source_code_folder_name_as_image_name,
+            image_name_randomizer: Rand::from_seed(Seed::get_value_from_current_time()),
+            deferred_seed: None,
+        }
     }
 
     fn image_name(&mut self) -> String {
@@ -61,21 +143,83 @@ impl Snapshot {
         Rand::from_seed(self.seed)
     }
 
-    pub fn capture_frame(&mut self, app: &nannou::prelude::App) {
-        image::capture_frame(self, app);
+    // Returns the name of the captured image.
+    pub fn capture_frame(
+        &mut self,
+        app: &nannou::prelude::App,
+        parameters: BTreeMap<String, ParameterValue>,
+    ) -> String {
+        self.save_if_deferred();
+
+        let image_name = image::capture_frame(self, app);
+        self.metadata
+            .record_image(&image_name, parameters, &self.post_processing);
+
+        self.did_capture_frames = true;
+        self.frame_number += 1;
+
+        image_name
+    }
+
+    // Returns the name of the saved image.
+    pub fn save_rasterized_frame(
+        &mut self,
+        rgba_image: &nannou::image::RgbaImage,
+        parameters: BTreeMap<String, ParameterValue>,
+    ) -> String {
+        self.save_if_deferred();
+
+        let image_name = image::save_rasterized_frame(self, rgba_image);
+        let image_hash = source_store::hash(rgba_image.as_raw());
+        self.metadata.record_rasterized_image(
+            &image_name,
+            image_hash,
+            parameters,
+            &self.post_processing,
+        );
 
         self.did_capture_frames = true;
         self.frame_number += 1;
+
+        image_name
     }
 }
 
 pub fn save() -> Snapshot {
-    Snapshot::create()
+    Snapshot::create(Seed::load())
+}
+
+// Waits until a frame is captured before saving anything.
+pub fn save_later() -> Snapshot {
+    Snapshot::deferred(Seed::load())
+}
+
+pub fn save_with_seed(seed: u64) -> Snapshot {
+    Snapshot::create(Seed::forced(seed))
 }
 
 pub fn exit(app: &nannou::prelude::App, model: crate::prelude::Model) {
-    if model.snapshot.did_capture_frames {
-        image::clean_up(app, &model.snapshot);
+    finish(app, &model.snapshot);
+}
+
+// Compresses the frames we captured, e.g. before moving on to a new snapshot.
+pub fn finish(app: &nannou::prelude::App, snapshot: &Snapshot) {
+    if snapshot.did_capture_frames {
+        report_compression_errors(image::clean_up(app, snapshot));
+    }
+}
+
+pub fn exit_headlessly(snapshot: &Snapshot) {
+    if snapshot.did_capture_frames {
+        report_compression_errors(image::compress_uncompressed_images(snapshot));
+    }
+}
+
+// We're about to exit, so there's nothing left to do except tell the user. The
+// uncompressed image is still in the images folder if this fails.
+fn report_compression_errors(result: Result<(), encoding::EncodingError>) {
+    if let Err(error) = result {
+        eprintln!("{error}");
     }
 }
 
@@ -5,15 +5,19 @@ use nannou::noise::Perlin;
 use nannou::noise::Seedable;
 use nannou::noise::SuperSimplex;
 use nanorand::Rng;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::fmt;
 use std::ops::RangeBounds;
 use std::ops::RangeInclusive;
 
-#[derive(Clone)]
+// Serializes as a RandState, so a Rand can be saved mid-stream (in checkpoints,
+// bug reports or test fixtures) and carry on from exactly the same point.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "RandState", from = "RandState")]
 pub struct Rand {
     seed: u64,
-    rng: RefCell<nanorand::WyRand>,
+    rng: RefCell<CountedWyRand>,
     perlin_noise_generator: RefCell<Option<Perlin>>,
     super_simplex_noise_generator: RefCell<Option<SuperSimplex>>,
     pub previous_alternation: RefCell<bool>,
@@ -25,6 +29,7 @@ impl fmt::Debug for Rand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Rand")
             .field("seed", &self.seed)
+            .field("num_generated", &self.rng.borrow().num_generated)
             .field("previous_alternation", &self.previous_alternation)
             .finish()
     }
@@ -32,7 +37,7 @@ impl fmt::Debug for Rand {
 
 impl Rand {
     pub fn from_seed(seed: u64) -> Rand {
-        let mut rng = nanorand::WyRand::new_seed(seed);
+        let mut rng = CountedWyRand::new_seed(seed);
         let previous_alternation = rng.generate::<usize>().is_even();
 
         Rand {
@@ -44,6 +49,66 @@ impl Rand {
         }
     }
 
+    // Every draw gets its own stretch of numbers, starting at the draw index times
+    // 2^32. No draw uses that many, so they never overlap, and each draw gets the
+    // same numbers however the draws are split into frames. The noise only depends
+    // on the seed, so it's the same for every draw.
+    pub fn for_draw(&self, draw_index: usize) -> Rand {
+        let mut rng = CountedWyRand::skipped_ahead(self.seed, (draw_index as u64) << 32);
+        let previous_alternation = rng.generate::<usize>().is_even();
+
+        Rand {
+            seed: self.seed,
+            rng: RefCell::new(rng),
+            perlin_noise_generator: RefCell::new(None),
+            super_simplex_noise_generator: RefCell::new(None),
+            previous_alternation: RefCell::new(previous_alternation),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Enough to recreate this Rand later and carry on from exactly the same point.
+    pub fn state(&self) -> RandState {
+        RandState {
+            seed: self.seed,
+            num_generated: self.rng.borrow().num_generated,
+            previous_alternation: *self.previous_alternation.borrow(),
+            perlin_seed: self
+                .perlin_noise_generator
+                .borrow()
+                .as_ref()
+                .map(|perlin| perlin.seed()),
+            super_simplex_seed: self
+                .super_simplex_noise_generator
+                .borrow()
+                .as_ref()
+                .map(|super_simplex| super_simplex.seed()),
+        }
+    }
+
+    pub fn from_state(state: &RandState) -> Rand {
+        let perlin_noise_generator = state
+            .perlin_seed
+            .map(|perlin_seed| Perlin::new().set_seed(perlin_seed));
+        let super_simplex_noise_generator = state
+            .super_simplex_seed
+            .map(|super_simplex_seed| SuperSimplex::new().set_seed(super_simplex_seed));
+
+        Rand {
+            seed: state.seed,
+            rng: RefCell::new(CountedWyRand::skipped_ahead(
+                state.seed,
+                state.num_generated,
+            )),
+            perlin_noise_generator: RefCell::new(perlin_noise_generator),
+            super_simplex_noise_generator: RefCell::new(super_simplex_noise_generator),
+            previous_alternation: RefCell::new(state.previous_alternation),
+        }
+    }
+
     pub fn sum_octaves<Noise>(
         &self,
         num_octaves: usize,
@@ -102,6 +167,13 @@ impl Rand {
         (random_number as f32) / (u16::MAX as f32)
     }
 
+    // Uses all 53 bits an f64 can hold, while zero_to_one only has 65,536 possible
+    // values. 0 inclusive to 1 exclusive.
+    pub fn zero_to_one_f64(&self) -> f64 {
+        let random_number = self.rng.borrow_mut().generate::<u64>();
+        (random_number >> 11) as f64 / (1u64 << 53) as f64
+    }
+
     pub fn range_f32(&self, range: &RangeInclusive<f32>) -> f32 {
         self.zero_to_one().denormalize(*range.start(), *range.end())
     }
@@ -252,6 +324,7 @@ impl Rand {
             .normalized_angle()
     }
 
+    // An estimate. For exact curl noise, use curl_xy or curl_xyz instead.
     pub fn curl<Noise>(&self, point: &Point2, noise: Noise) -> Point2
     where
         Noise: Fn(f64, f64) -> f64,
@@ -515,3 +588,115 @@ impl Iterator for NormalizedXyWalkerWithMomentum {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RandState {
+    pub seed: u64,
+    // How many numbers the underlying WyRand has generated.
+    pub num_generated: u64,
+    pub previous_alternation: bool,
+    // The noise generators get created the first time they're used. These are
+    // None until then.
+    #[serde(default)]
+    pub perlin_seed: Option<u32>,
+    #[serde(default)]
+    pub super_simplex_seed: Option<u32>,
+}
+
+impl From<Rand> for RandState {
+    fn from(rand: Rand) -> Self {
+        rand.state()
+    }
+}
+
+impl From<RandState> for Rand {
+    fn from(state: RandState) -> Self {
+        Rand::from_state(&state)
+    }
+}
+
+// Generates exactly the same numbers as WyRand, but counts how many it has
+// generated. WyRand doesn't let us read its state, but its state is just the seed
+// plus a constant for every number generated, so the count is enough to restore it.
+// What WyRand adds to its state every time it generates a number.
+const wy_rand_increment: u64 = 0xa0761d6478bd642f;
+
+#[derive(Clone)]
+struct CountedWyRand {
+    rng: nanorand::WyRand,
+    num_generated: u64,
+}
+
+impl CountedWyRand {
+    fn new_seed(seed: u64) -> CountedWyRand {
+        CountedWyRand {
+            rng: nanorand::WyRand::new_seed(seed),
+            num_generated: 0,
+        }
+    }
+
+    fn skipped_ahead(seed: u64, num_generated: u64) -> CountedWyRand {
+        let state = seed.wrapping_add(num_generated.wrapping_mul(wy_rand_increment));
+
+        CountedWyRand {
+            rng: nanorand::WyRand::new_seed(state),
+            num_generated,
+        }
+    }
+}
+
+impl Rng<8> for CountedWyRand {
+    fn rand(&mut self) -> [u8; 8] {
+        self.num_generated += 1;
+        self.rng.rand()
+    }
+
+    fn rand_with_seed(seed: &[u8]) -> Self {
+        CountedWyRand {
+            rng: nanorand::WyRand::rand_with_seed(seed),
+            num_generated: 0,
+        }
+    }
+
+    // We never reseed. If we did, the count would no longer lead back to this
+    // state from Rand's seed.
+    fn reseed(&mut self, new_seed: &[u8]) {
+        self.rng.reseed(new_seed);
+        self.num_generated = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn restored_rand_carries_on_from_the_same_point() {
+        let rand = Rand::from_seed(7);
+        for _ in 0..100 {
+            rand.zero_to_one();
+            rand.range(0..10);
+            rand.alternate();
+        }
+        rand.perlin_xy(&pt2(0.3, 0.4));
+        rand.super_simplex_xy(&pt2(0.3, 0.4));
+
+        let json = serde_json::to_string(&rand).unwrap();
+        let restored: Rand = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(rand.state(), restored.state());
+
+        for _ in 0..100 {
+            assert_eq!(rand.zero_to_one(), restored.zero_to_one());
+            assert_eq!(rand.range(0..1000), restored.range(0..1000));
+            assert_eq!(rand.alternate(), restored.alternate());
+        }
+
+        let point = pt2(0.1, 0.2);
+        assert_eq!(rand.perlin_xy(&point), restored.perlin_xy(&point));
+        assert_eq!(
+            rand.super_simplex_xy(&point),
+            restored.super_simplex_xy(&point)
+        );
+    }
+}
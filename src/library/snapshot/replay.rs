@@ -0,0 +1,244 @@
+use super::manifest;
+use super::metadata::SnapshotMetadata;
+use super::source_store::{self, SourceManifest};
+use duct::cmd;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const parameters_file_name: &str = "parameters.json";
+const artwork_config_file_name: &str = "artwork_config.json";
+const post_processing_file_name: &str = "post_processing.json";
+
+// Rebuilds an archived snapshot from its own source code and seed and renders it
+// again, headlessly. Everything happens in a scratch workspace under target/, so
+// src/, examples/ and images/ are left alone.
+//
+// If the original render was headless, we also check that we got exactly the same
+// pixels.
+pub fn replay(snapshot_name: &str) {
+    let original = SnapshotMetadata::load(snapshot_name)
+        .unwrap_or_else(|| panic!("Couldn't find snapshot.json for {snapshot_name}."));
+
+    let workspace = workspace_folder(snapshot_name);
+    prepare_workspace(&original, &workspace);
+    let archived_flags = save_archived_inputs(&original, &workspace);
+
+    println!("Replaying {snapshot_name} in {}.", workspace.display());
+
+    let arguments = replay_arguments(&original, archived_flags);
+    cmd("cargo", arguments)
+        .dir(&workspace)
+        // Shared between replays, so we only compile our dependencies once.
+        .env(
+            "CARGO_TARGET_DIR",
+            manifest::folder().join("target").join("replay_target"),
+        )
+        .run()
+        .unwrap_or_else(|error| panic!("Couldn't build and render {snapshot_name}: {error}"));
+
+    let replayed = SnapshotMetadata::load_all_in(&workspace.join("examples"))
+        .pop()
+        .expect("The replay didn't save a snapshot.");
+
+    check_image_hashes(&original, &replayed, &workspace);
+}
+
+fn workspace_folder(snapshot_name: &str) -> PathBuf {
+    manifest::folder()
+        .join("target")
+        .join("replay")
+        .join(snapshot_name)
+}
+
+fn prepare_workspace(original: &SnapshotMetadata, workspace: &Path) {
+    let snapshot_name = &original.friendly_name;
+
+    // Start from scratch so nothing from an earlier replay leaks in.
+    if workspace.exists() {
+        fs::remove_dir_all(workspace).expect("Couldn't clear the replay workspace.");
+    }
+
+    let source_folder = workspace.join("src");
+    fs::create_dir_all(&source_folder).expect("Couldn't create the replay workspace.");
+    fs::create_dir_all(workspace.join("images")).unwrap();
+
+    // Copying the lock file keeps dependency versions the same.
+    let lock_file_path = manifest::folder().join("Cargo.lock");
+    if lock_file_path.exists() {
+        fs::copy(lock_file_path, workspace.join("Cargo.lock")).unwrap();
+    }
+
+    let mut options = fs_extra::dir::CopyOptions::new();
+    options.content_only = true;
+
+    if SourceManifest::load(snapshot_name).is_some() {
+        source_store::materialize(snapshot_name, &source_folder);
+    } else if original.artwork_config.is_some() {
+        // Snapshots from --artwork-config only archive the config, so they get
+        // rendered with the current source code. The git commit in snapshot.json
+        // says which code they were made with, if this doesn't match.
+        fs_extra::dir::copy(manifest::folder().join("src"), &source_folder, &options)
+            .expect("Couldn't copy the source code.");
+        fs::write(source_folder.join("seed"), original.seed.to_string())
+            .expect("Couldn't save the seed.");
+    } else {
+        // Older snapshots are full copies of src/.
+        let snapshot_folder = manifest::folder().join("examples").join(snapshot_name);
+        fs_extra::dir::copy(snapshot_folder, &source_folder, &options)
+            .expect("Couldn't copy the snapshot's source code.");
+    }
+
+    save_manifest(workspace);
+
+    // Seed::load reads this, so without it we'd get a new seed.
+    if !source_folder.join("seed").exists() {
+        panic!("{snapshot_name} doesn't have a seed file, so it can't be reproduced.");
+    }
+}
+
+// We don't archive the manifest, so use the current one. Older snapshots don't have
+// the source for newer example targets though, like the hot reloaded artwork, and
+// cargo won't build with a target that's missing. Those get left out.
+fn save_manifest(workspace: &Path) {
+    let text = fs::read_to_string(manifest::folder().join("Cargo.toml"))
+        .expect("Couldn't read Cargo.toml.");
+    let mut cargo_toml: toml::Value = toml::from_str(&text).expect("Couldn't parse Cargo.toml.");
+
+    if let Some(toml::Value::Array(examples)) = cargo_toml.get_mut("example") {
+        examples.retain(|example| {
+            example
+                .get("path")
+                .and_then(|path| path.as_str())
+                .map_or(true, |path| workspace.join(path).exists())
+        });
+    }
+
+    let text = toml::to_string(&cargo_toml).expect("Couldn't serialize Cargo.toml.");
+    fs::write(workspace.join("Cargo.toml"), text).expect("Couldn't save Cargo.toml.");
+}
+
+// The original might have used values from the parameter panel or an artwork
+// config, which aren't in the source code. We write them into the workspace and
+// return the flags that point at them.
+fn save_archived_inputs(original: &SnapshotMetadata, workspace: &Path) -> Vec<String> {
+    let mut flags = vec![];
+
+    // Only the first image gets compared, so that's the one whose inputs we want.
+    let image_name = original.images.first();
+    let parameters = image_name.and_then(|image_name| original.image_parameters.get(image_name));
+    let post_processing =
+        image_name.and_then(|image_name| original.image_post_processing.get(image_name));
+
+    if let Some(parameters) = parameters {
+        let json =
+            serde_json::to_string_pretty(parameters).expect("Couldn't serialize the parameters.");
+        fs::write(workspace.join(parameters_file_name), json)
+            .expect("Couldn't save the parameters.");
+
+        flags.extend(["--parameters".to_string(), parameters_file_name.to_string()]);
+    }
+
+    if let Some(artwork_config) = &original.artwork_config {
+        let json = serde_json::to_string_pretty(artwork_config)
+            .expect("Couldn't serialize the artwork config.");
+        fs::write(workspace.join(artwork_config_file_name), json)
+            .expect("Couldn't save the artwork config.");
+
+        flags.extend([
+            "--artwork-config".to_string(),
+            artwork_config_file_name.to_string(),
+        ]);
+    }
+
+    // Might have come from the code, but replacing it with the same filters is harmless.
+    if let Some(post_processing) = post_processing {
+        let json = serde_json::to_string_pretty(post_processing)
+            .expect("Couldn't serialize the post-processing filters.");
+        fs::write(workspace.join(post_processing_file_name), json)
+            .expect("Couldn't save the post-processing filters.");
+
+        flags.extend([
+            "--post-process".to_string(),
+            post_processing_file_name.to_string(),
+        ]);
+    }
+
+    flags
+}
+
+// Render headlessly, with whatever other flags the original run used. Snapshots from
+// before headless rendering existed ignore --headless and open a window instead.
+fn replay_arguments(original: &SnapshotMetadata, archived_flags: Vec<String>) -> Vec<String> {
+    let mut arguments: Vec<String> = vec!["run", "--release", "--", "--headless"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    // The seed is already in the restored seed file, and we only want to render
+    // this one snapshot, not the whole sweep it came from.
+    // Archived inputs get replaced by the copies we saved in the workspace.
+    let flags_with_a_value = [
+        "--seed",
+        "--seeds",
+        "--random-seeds",
+        "--parameters",
+        "--artwork-config",
+        "--post-process",
+    ];
+
+    let mut original_flags = original.command_line_flags.iter();
+    while let Some(flag) = original_flags.next() {
+        if flags_with_a_value.contains(&flag.as_str()) {
+            original_flags.next();
+        } else if flag != "--headless" {
+            arguments.push(flag.clone());
+        }
+    }
+
+    arguments.extend(archived_flags);
+
+    arguments
+}
+
+fn check_image_hashes(original: &SnapshotMetadata, replayed: &SnapshotMetadata, workspace: &Path) {
+    let images_folder = workspace.join("images");
+
+    let original_hash = original
+        .images
+        .first()
+        .and_then(|image_name| original.image_hashes.get(image_name));
+
+    let replayed_hash = replayed
+        .images
+        .first()
+        .and_then(|image_name| replayed.image_hashes.get(image_name));
+
+    match (original_hash, replayed_hash) {
+        (Some(original_hash), Some(replayed_hash)) if original_hash == replayed_hash => {
+            println!(
+                "Replay matches {}. Saved to {}.",
+                original.friendly_name,
+                images_folder.display()
+            );
+        }
+        (Some(original_hash), Some(replayed_hash)) => {
+            eprintln!(
+                "Replay doesn't match {}. Expected {original_hash}, got {replayed_hash}. Saved to {}.",
+                original.friendly_name,
+                images_folder.display()
+            );
+            std::process::exit(1);
+        }
+        (None, _) => {
+            println!(
+                "{} wasn't rendered headlessly, so there's no hash to compare against. Saved to {}.",
+                original.friendly_name,
+                images_folder.display()
+            );
+        }
+        (Some(_), None) => {
+            eprintln!("The replay didn't render an image.");
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::library::command_line;
+use nanorand::Rng;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
@@ -5,18 +7,18 @@ use std::path::PathBuf;
 pub struct Seed {
     pub value: u64,
     save_path: Option<PathBuf>,
+    // What was in the seed file before we overwrote it with a forced seed, so it
+    // can be put back afterwards.
+    previous_file_contents: Option<String>,
 }
 
 impl Seed {
     pub fn load() -> Seed {
-        let path = Path::new(file!())
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("seed");
+        if let Some(value) = Seed::get_value_from_command_line() {
+            return Seed::forced(value);
+        }
+
+        let path = Seed::file_path();
 
         let save_path;
 
@@ -32,7 +34,36 @@ impl Seed {
             Seed::get_value_from_current_time()
         };
 
-        Seed { value, save_path }
+        Seed {
+            value,
+            save_path,
+            previous_file_contents: None,
+        }
+    }
+
+    // Ignores any seed file and uses this value instead. It still gets saved with
+    // the source code, so the snapshot can be reproduced later. That means
+    // overwriting the seed file until the source code is saved.
+    pub fn forced(value: u64) -> Seed {
+        let path = Seed::file_path();
+        let previous_file_contents = fs::read_to_string(&path).ok();
+
+        Seed {
+            value,
+            save_path: Some(path),
+            previous_file_contents,
+        }
+    }
+
+    fn file_path() -> PathBuf {
+        Path::new(file!())
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("seed")
     }
 
     pub fn save_to_file(&self) {
@@ -50,9 +81,13 @@ impl Seed {
         // with our source code.
         //
         // We delete it here so that we get a new seed
-        // next time we compile.
+        // next time we compile. Unless we overwrote
+        // one, which gets put back.
         if let Some(path) = &self.save_path {
-            fs::remove_file(path).unwrap();
+            match &self.previous_file_contents {
+                Some(previous_file_contents) => fs::write(path, previous_file_contents).unwrap(),
+                None => fs::remove_file(path).unwrap(),
+            }
         }
     }
 
@@ -60,6 +95,44 @@ impl Seed {
         chrono::Local::now().timestamp_nanos() as u64
     }
 
+    // --seed 1234
+    pub fn get_value_from_command_line() -> Option<u64> {
+        command_line::value_of("--seed").map(|value| {
+            value
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("--seed expects a whole number, got {value}."))
+        })
+    }
+
+    // --seeds 10..20 renders seeds 10 through 19. --random-seeds 8 renders eight
+    // random seeds. Returns None if neither flag was passed.
+    pub fn get_sweep_values_from_command_line() -> Option<Vec<u64>> {
+        if let Some(range) = command_line::value_of("--seeds") {
+            let parse = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .unwrap_or_else(|_| panic!("--seeds expects a range like 10..20, got {range}."))
+            };
+
+            let (start, end) = range
+                .split_once("..")
+                .unwrap_or_else(|| panic!("--seeds expects a range like 10..20, got {range}."));
+
+            return Some((parse(start)..parse(end)).collect());
+        }
+
+        if let Some(count) = command_line::value_of("--random-seeds") {
+            let count = count
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("--random-seeds expects a count, got {count}."));
+
+            let mut rng = nanorand::WyRand::new_seed(Seed::get_value_from_current_time());
+            return Some((0..count).map(|_| rng.generate::<u64>()).collect());
+        }
+
+        None
+    }
+
     pub fn get_value_from_file(path: &Path) -> u64 {
         let value = fs::read_to_string(path).unwrap();
         let value = value.trim();
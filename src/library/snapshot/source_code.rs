@@ -1,17 +1,46 @@
 use super::manifest;
-use std::path::Path;
+use super::source_store::{self, SourceManifest};
 
-fn copy_contents(from: &Path, to: &Path) {
-    let mut options = fs_extra::dir::CopyOptions::new();
-    options.content_only = true;
+pub fn save_current_version(snapshot_name: &str) {
+    let source_folder = manifest::folder().join("src");
 
-    fs_extra::dir::create_all(&to, false).unwrap();
-    fs_extra::dir::copy(&from, to, &options).unwrap();
+    source_store::store_folder(&source_folder).save(snapshot_name);
 }
 
-pub fn save_current_version(snapshot_name: &str) {
-    let source_folder = manifest::folder().join("src");
+// Restores a snapshot's source code and seed into its folder in examples/, so it
+// can be run again with `cargo run --release --example <snapshot_name>`.
+pub fn materialize(snapshot_name: &str) {
     let target_folder = manifest::folder().join("examples").join(snapshot_name);
 
-    copy_contents(&source_folder, &target_folder);
+    source_store::materialize(snapshot_name, &target_folder);
+
+    println!("Restored {snapshot_name} into {}.", target_folder.display());
+}
+
+pub fn print_changes(from_snapshot_name: &str, to_snapshot_name: &str) {
+    let changed_files = source_store::changed_files(from_snapshot_name, to_snapshot_name);
+
+    if changed_files.is_empty() {
+        println!("No changes between {from_snapshot_name} and {to_snapshot_name}.");
+    }
+
+    for relative_path in changed_files {
+        println!("{relative_path}");
+    }
+}
+
+// Whether src/ still matches the source code saved with the snapshot. None if the
+// snapshot doesn't have a source manifest. The seed file only exists in src/ while
+// a snapshot is being saved, so we leave it out.
+pub fn has_changed_since(snapshot_name: &str) -> Option<bool> {
+    let saved = SourceManifest::load(snapshot_name)?;
+    let current = source_store::hash_folder(&manifest::folder().join("src"));
+
+    let without_seed = |source_manifest: SourceManifest| {
+        let mut files = source_manifest.files;
+        files.remove("seed");
+        files
+    };
+
+    Some(without_seed(saved) != without_seed(current))
 }
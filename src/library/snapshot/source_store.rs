@@ -0,0 +1,238 @@
+use super::manifest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const manifest_file_name: &str = "sources.json";
+
+// Almost every file in a snapshot is identical to the same file in the previous
+// snapshot. So instead of copying the whole src/ folder every time, we store each
+// file once, named after the hash of its contents, and give each snapshot a small
+// manifest pointing at the files it needs.
+//
+//   snapshots/objects/ab/cdef...     File contents, keyed by hash.
+//   examples/<name>/sources.json     Maps relative paths to hashes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SourceManifest {
+    pub files: BTreeMap<String, String>,
+}
+
+impl SourceManifest {
+    pub fn load(snapshot_name: &str) -> Option<SourceManifest> {
+        let json = fs::read_to_string(manifest_path(snapshot_name)).ok()?;
+        let source_manifest = serde_json::from_str(&json).unwrap_or_else(|error| {
+            panic!("Couldn't parse {manifest_file_name} for {snapshot_name}: {error}")
+        });
+
+        Some(source_manifest)
+    }
+
+    pub fn save(&self, snapshot_name: &str) {
+        let path = manifest_path(snapshot_name);
+        fs::create_dir_all(path.parent().unwrap()).expect("Couldn't create the snapshot folder.");
+
+        let json = serde_json::to_string_pretty(self).expect("Couldn't serialize the manifest.");
+        fs::write(path, json).expect("Couldn't save the source manifest.");
+    }
+}
+
+// Stores every file in the folder and returns a manifest pointing at them.
+pub fn store_folder(folder: &Path) -> SourceManifest {
+    let mut source_manifest = SourceManifest::default();
+
+    for path in files_in(folder) {
+        let contents = fs::read(&path).expect("Couldn't read a file we're storing.");
+        let hash = store_contents(&contents);
+
+        source_manifest
+            .files
+            .insert(relative_path(folder, &path), hash);
+    }
+
+    source_manifest
+}
+
+// Like store_folder, but only works out the hashes without storing anything.
+pub fn hash_folder(folder: &Path) -> SourceManifest {
+    let mut source_manifest = SourceManifest::default();
+
+    for path in files_in(folder) {
+        let contents = fs::read(&path).expect("Couldn't read a file we're hashing.");
+        source_manifest
+            .files
+            .insert(relative_path(folder, &path), hash(&contents));
+    }
+
+    source_manifest
+}
+
+// Recreates a snapshot's files in the destination folder, e.g. so it can be built
+// with cargo run --example.
+pub fn materialize(snapshot_name: &str, destination: &Path) {
+    let source_manifest = SourceManifest::load(snapshot_name)
+        .unwrap_or_else(|| panic!("Couldn't find a source manifest for {snapshot_name}."));
+
+    for (relative_path, hash) in &source_manifest.files {
+        let path = destination.join(relative_path);
+        fs::create_dir_all(path.parent().unwrap()).expect("Couldn't create a folder.");
+        fs::copy(object_path(hash), &path)
+            .unwrap_or_else(|error| panic!("Couldn't restore {relative_path}: {error}"));
+    }
+}
+
+// Paths of files that were added, removed or changed between two snapshots. Cheap,
+// since we only have to compare hashes.
+pub fn changed_files(from_snapshot_name: &str, to_snapshot_name: &str) -> Vec<String> {
+    let load = |snapshot_name: &str| {
+        SourceManifest::load(snapshot_name)
+            .unwrap_or_else(|| panic!("Couldn't find a source manifest for {snapshot_name}."))
+    };
+
+    let from = load(from_snapshot_name).files;
+    let to = load(to_snapshot_name).files;
+
+    let mut changed: Vec<String> = from
+        .keys()
+        .chain(to.keys())
+        .filter(|relative_path| from.get(*relative_path) != to.get(*relative_path))
+        .cloned()
+        .collect();
+
+    changed.sort();
+    changed.dedup();
+
+    changed
+}
+
+// Converts examples/ folders that contain full copies of src/ into manifests. The
+// copied files are removed once they're safely in the store.
+pub fn deduplicate_examples() {
+    let examples_folder = manifest::folder().join("examples");
+
+    for entry in fs::read_dir(&examples_folder).expect("Couldn't find the examples folder.") {
+        let snapshot_folder = entry.unwrap().path();
+        let snapshot_name = snapshot_folder
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let already_deduplicated = manifest_path(&snapshot_name).exists();
+        let has_source_code = snapshot_folder.join("main.rs").exists();
+        if already_deduplicated || !has_source_code {
+            continue;
+        }
+
+        let source_manifest = store_folder(&snapshot_folder);
+
+        // Make sure everything can be read back before deleting anything.
+        for (relative_path, hash) in &source_manifest.files {
+            let original = fs::read(snapshot_folder.join(relative_path)).unwrap();
+            let stored = fs::read(object_path(hash)).unwrap();
+            assert_eq!(
+                original, stored,
+                "Stored copy of {relative_path} doesn't match."
+            );
+        }
+
+        source_manifest.save(&snapshot_name);
+
+        for relative_path in source_manifest.files.keys() {
+            if is_source_code(relative_path) {
+                fs::remove_file(snapshot_folder.join(relative_path)).unwrap();
+            }
+        }
+
+        remove_empty_folders(&snapshot_folder);
+
+        println!("Deduplicated {snapshot_name}.");
+    }
+}
+
+fn store_contents(contents: &[u8]) -> String {
+    let hash = hash(contents);
+    let path = object_path(&hash);
+
+    if !path.exists() {
+        fs::create_dir_all(path.parent().unwrap()).expect("Couldn't create the objects folder.");
+        fs::write(&path, contents).expect("Couldn't store a file.");
+    }
+
+    hash
+}
+
+pub fn hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+fn object_path(hash: &str) -> PathBuf {
+    let (prefix, rest) = hash.split_at(2);
+    store_folder_path().join("objects").join(prefix).join(rest)
+}
+
+pub fn store_folder_path() -> PathBuf {
+    manifest::folder().join("snapshots")
+}
+
+fn manifest_path(snapshot_name: &str) -> PathBuf {
+    manifest::folder()
+        .join("examples")
+        .join(snapshot_name)
+        .join(manifest_file_name)
+}
+
+fn files_in(folder: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+
+    for entry in fs::read_dir(folder).expect("Couldn't read a folder we're storing.") {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            files.append(&mut files_in(&path));
+        } else if !is_snapshot_bookkeeping(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    files
+}
+
+// Files that describe the snapshot rather than belonging to its source code.
+fn is_snapshot_bookkeeping(path: &Path) -> bool {
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    file_name == manifest_file_name || file_name == "snapshot.json"
+}
+
+// We only remove source code. The seed file stays where it is, so the folder still
+// tells you how to reproduce the snapshot at a glance.
+fn is_source_code(relative_path: &str) -> bool {
+    relative_path.ends_with(".rs")
+}
+
+fn relative_path(folder: &Path, path: &Path) -> String {
+    path.strip_prefix(folder)
+        .unwrap()
+        .components()
+        .map(|component| component.as_os_str().to_str().unwrap())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn remove_empty_folders(folder: &Path) {
+    for entry in fs::read_dir(folder).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            remove_empty_folders(&path);
+
+            if fs::read_dir(&path).unwrap().next().is_none() {
+                fs::remove_dir(&path).unwrap();
+            }
+        }
+    }
+}
@@ -1,95 +1,369 @@
-use crate::snapshot::manifest;
+use super::encoding::EncodingError;
+use super::image::{find_image, images_folder_path};
+use super::manifest;
+use super::metadata::SnapshotMetadata;
+use crate::library::command_line;
+use chrono::{DateTime, Utc};
 use duct::cmd;
-use std::io::{BufRead, BufReader};
-use std::{fs, fs::DirEntry, io::ErrorKind, time::SystemTime};
+use nannou::image::{self, imageops, Rgba, RgbaImage};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 
-use itertools::Itertools;
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimelapseFormat {
+    Gif,
+    Apng,
+    // Needs ffmpeg to be installed.
+    Mp4,
+}
+
+impl TimelapseFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            TimelapseFormat::Gif => "gif",
+            TimelapseFormat::Apng => "png",
+            TimelapseFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+pub struct TimelapseOptions {
+    // GIFs can only get close, since their frame delays are in whole hundredths of a
+    // second. 15 plays at 100 / 7, or about 14.3.
+    pub frames_per_second: u32,
+    // Defaults to the width of the first image, up to 1080 pixels. The height
+    // follows from the first image's aspect ratio, and images with other aspect
+    // ratios get letterboxed.
+    pub width: Option<u32>,
+    // How many in-between frames to blend from one image to the next.
+    pub num_crossfade_frames: usize,
+    pub format: TimelapseFormat,
+}
+
+impl TimelapseOptions {
+    // --create-timelapse [--fps 15] [--width 1080] [--crossfade 4] [--format gif|apng|mp4]
+    pub fn from_command_line() -> TimelapseOptions {
+        let parse = |flag: &str| {
+            command_line::value_of(flag).map(|value| {
+                value
+                    .parse::<u32>()
+                    .unwrap_or_else(|_| panic!("{flag} expects a whole number, got {value}."))
+            })
+        };
+
+        let format = match command_line::value_of("--format").as_deref() {
+            None | Some("gif") => TimelapseFormat::Gif,
+            Some("apng") => TimelapseFormat::Apng,
+            Some("mp4") => TimelapseFormat::Mp4,
+            Some(other) => panic!("Unknown timelapse format {other}. Try gif, apng or mp4."),
+        };
+
+        TimelapseOptions {
+            frames_per_second: parse("--fps").unwrap_or(15),
+            width: parse("--width"),
+            num_crossfade_frames: parse("--crossfade").unwrap_or(0) as usize,
+            format,
+        }
+    }
+}
 
-pub fn create() {
-    let temp_folder = manifest::folder().join("temp");
-    if let Err(error) = fs::remove_dir_all(temp_folder.clone()) {
-        match error.kind() {
-            ErrorKind::NotFound => {
-                // No problem. Keep going.
+pub fn create() -> Result<(), EncodingError> {
+    let options = TimelapseOptions::from_command_line();
+
+    let image_paths = image_paths_in_order()?;
+    if image_paths.is_empty() {
+        println!("There aren't any images to make a timelapse from.");
+        return Ok(());
+    }
+
+    let (width, height) = frame_size(&image_paths[0], &options)?;
+
+    let timelapses_folder = manifest::folder().join("timelapses");
+    fs::create_dir_all(&timelapses_folder)?;
+
+    let artwork_name = manifest::name();
+    let path = timelapses_folder.join(format!("{artwork_name}.{}", options.format.extension()));
+
+    let num_frames = image_paths.len() + (image_paths.len() - 1) * options.num_crossfade_frames;
+    let mut frame_writer = FrameWriter::new(&path, width, height, num_frames, &options)?;
+
+    let mut previous_frame: Option<RgbaImage> = None;
+
+    for (index, image_path) in image_paths.iter().enumerate() {
+        println!("Adding frame {} of {}.", index + 1, image_paths.len());
+
+        let frame = letterboxed(&image::open(image_path)?.to_rgba8(), width, height);
+
+        if let Some(previous_frame) = &previous_frame {
+            for crossfade_index in 1..=options.num_crossfade_frames {
+                let amount = crossfade_index as f32 / (options.num_crossfade_frames + 1) as f32;
+                frame_writer.write(&crossfade(previous_frame, &frame, amount))?;
             }
-            _ => {
-                println!("{error}");
-                panic!("Error removing temp folder.");
+        }
+
+        frame_writer.write(&frame)?;
+        previous_frame = Some(frame);
+    }
+
+    frame_writer.finish(&path, &options)?;
+
+    println!("Saved the timelapse to {}.", path.display());
+
+    // Open the timelapse we just created. There's no equivalent of open we can
+    // count on elsewhere.
+    if cfg!(target_os = "macos") {
+        cmd!("open", &path).run()?;
+    }
+
+    Ok(())
+}
+
+// Images with a snapshot.json are ordered by when their snapshot was created.
+// Older images without one fall back to when the file was last modified, since
+// creation times aren't available on many Linux filesystems.
+fn image_paths_in_order() -> Result<Vec<PathBuf>, EncodingError> {
+    let mut timestamped_paths: Vec<(DateTime<Utc>, PathBuf)> = vec![];
+    let mut paths_with_metadata = HashSet::new();
+
+    for metadata in SnapshotMetadata::load_all() {
+        let created_at = DateTime::parse_from_rfc3339(&metadata.created_at)?.with_timezone(&Utc);
+
+        for image_name in &metadata.images {
+            if let Some(path) = find_image(image_name) {
+                paths_with_metadata.insert(path.clone());
+                timestamped_paths.push((created_at, path));
             }
         }
-    };
-    fs::create_dir(temp_folder.clone()).expect("Couldn't create the temp folder.");
-
-    let path_to_images_folder = super::image::images_folder_path();
-
-    let mut image_path_dir_entries = fs::read_dir(path_to_images_folder)
-        .expect("Couldn't find images folder. Does it exist?")
-        .filter(|file_path| !is_ds_store(file_path.as_ref().unwrap()))
-        .collect_vec();
-
-    image_path_dir_entries.sort_by(|a, b| {
-        let a_created_time = get_created_time(a.as_ref().unwrap());
-        let b_created_time = get_created_time(b.as_ref().unwrap());
-        a_created_time.cmp(&b_created_time)
-    });
-
-    for (index, dir_entry) in image_path_dir_entries.into_iter().enumerate() {
-        let padded_index = pad_number(index);
-        let new_name = format!("{padded_index}.tif");
-        let original_path = dir_entry.unwrap().path();
-        let new_path = temp_folder.join(new_name);
-        fs::copy(original_path, new_path).expect("Couldn't copy image file.");
     }
 
-    let temp_images_path = temp_folder.join("*.tif");
+    for entry in fs::read_dir(images_folder_path()).expect("Couldn't find the images folder.") {
+        let entry = entry.unwrap();
+        let path = entry.path();
 
-    let artwork_name = manifest::name();
-    let video_file_name = format!("{artwork_name}.mp4");
-    let video_path = temp_folder.join(video_file_name);
-    let video_path_string = video_path.to_str().unwrap();
+        if is_image(&path) && !paths_with_metadata.contains(&path) {
+            let modified_at = entry.metadata().unwrap().modified().unwrap();
+            timestamped_paths.push((modified_at.into(), path));
+        }
+    }
+
+    // Stable, so images from the same snapshot stay in the order they were captured.
+    timestamped_paths.sort_by_key(|(timestamp, _)| *timestamp);
+
+    Ok(timestamped_paths
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
+
+fn is_image(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    matches!(extension, Some("tif") | Some("png"))
+}
+
+fn frame_size(
+    first_image_path: &Path,
+    options: &TimelapseOptions,
+) -> Result<(u32, u32), EncodingError> {
+    let (image_width, image_height) = image::image_dimensions(first_image_path)?;
+
+    let width = options.width.unwrap_or_else(|| image_width.min(1080));
+    let height = (width as f32 * image_height as f32 / image_width as f32).round() as u32;
+
+    // Video encoders want even dimensions.
+    Ok((round_down_to_even(width), round_down_to_even(height)))
+}
+
+// Scales the image to fit inside the frame without stretching it, and fills the
+// rest with black.
+fn letterboxed(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let scale = (width as f32 / image.width() as f32).min(height as f32 / image.height() as f32);
+    let fitted_width = ((image.width() as f32 * scale).round() as u32).clamp(1, width);
+    let fitted_height = ((image.height() as f32 * scale).round() as u32).clamp(1, height);
+
+    let fitted = imageops::resize(
+        image,
+        fitted_width,
+        fitted_height,
+        imageops::FilterType::Triangle,
+    );
 
+    let mut frame = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    imageops::replace(
+        &mut frame,
+        &fitted,
+        (width - fitted_width) / 2,
+        (height - fitted_height) / 2,
+    );
+
+    frame
+}
+
+fn round_down_to_even(number: u32) -> u32 {
+    (number - number % 2).max(2)
+}
+
+fn gif_dimensions(width: u32, height: u32) -> Result<(u16, u16), EncodingError> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(gif_width), Ok(gif_height)) => Ok((gif_width, gif_height)),
+        _ => Err(EncodingError::TooBigForGif { width, height }),
+    }
+}
+
+fn crossfade(from: &RgbaImage, to: &RgbaImage, amount: f32) -> RgbaImage {
+    let mut blended = from.clone();
+
+    for (blended_channel, to_channel) in blended.iter_mut().zip(to.iter()) {
+        let from_channel = *blended_channel as f32;
+        *blended_channel = (from_channel + (*to_channel as f32 - from_channel) * amount) as u8;
+    }
+
+    blended
+}
+
+enum FrameWriter {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        // In hundredths of a second.
+        frame_delay: u16,
+    },
+    Apng(png::Writer<BufWriter<File>>),
+    // Numbered PNGs for ffmpeg to turn into a video.
+    PngSequence {
+        folder: PathBuf,
+        num_frames: usize,
+    },
+}
+
+impl FrameWriter {
+    fn new(
+        path: &Path,
+        width: u32,
+        height: u32,
+        num_frames: usize,
+        options: &TimelapseOptions,
+    ) -> Result<FrameWriter, EncodingError> {
+        match options.format {
+            TimelapseFormat::Gif => {
+                let (gif_width, gif_height) = gif_dimensions(width, height)?;
+
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = gif::Encoder::new(file, gif_width, gif_height, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+
+                // Browsers play anything under 2 hundredths of a second at 10, so a
+                // faster timelapse would end up slower.
+                let frames_per_second = options.frames_per_second.max(1);
+                let frame_delay = ((100.0 / frames_per_second as f32).round() as u16).max(2);
+
+                if u64::from(frame_delay) * u64::from(frames_per_second) != 100 {
+                    let actual_frames_per_second = 100.0 / frame_delay as f32;
+                    println!("GIF frame delays are in hundredths of a second, so the timelapse will play at {actual_frames_per_second:.1} fps instead of {frames_per_second}.");
+                }
+
+                Ok(FrameWriter::Gif {
+                    encoder,
+                    frame_delay,
+                })
+            }
+
+            TimelapseFormat::Apng => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = png::Encoder::new(file, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(num_frames as u32, 0)?; // 0 loops forever.
+                let frames_per_second =
+                    u16::try_from(options.frames_per_second).unwrap_or(u16::MAX);
+                encoder.set_frame_delay(1, frames_per_second)?;
+
+                Ok(FrameWriter::Apng(encoder.write_header()?))
+            }
+
+            TimelapseFormat::Mp4 => {
+                let folder = manifest::folder().join("temp").join("timelapse_frames");
+                if folder.exists() {
+                    fs::remove_dir_all(&folder)?;
+                }
+                fs::create_dir_all(&folder)?;
+
+                Ok(FrameWriter::PngSequence {
+                    folder,
+                    num_frames: 0,
+                })
+            }
+        }
+    }
+
+    fn write(&mut self, frame: &RgbaImage) -> Result<(), EncodingError> {
+        match self {
+            FrameWriter::Gif {
+                encoder,
+                frame_delay,
+            } => {
+                let (gif_width, gif_height) = gif_dimensions(frame.width(), frame.height())?;
+                let mut pixels = frame.as_raw().clone();
+                let mut gif_frame =
+                    gif::Frame::from_rgba_speed(gif_width, gif_height, &mut pixels, 10);
+                gif_frame.delay = *frame_delay;
+                encoder.write_frame(&gif_frame)?;
+            }
+
+            FrameWriter::Apng(writer) => {
+                writer.write_image_data(frame.as_raw())?;
+            }
+
+            FrameWriter::PngSequence { folder, num_frames } => {
+                frame.save(folder.join(format!("{:05}.png", num_frames)))?;
+                *num_frames += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, path: &Path, options: &TimelapseOptions) -> Result<(), EncodingError> {
+        match self {
+            FrameWriter::Gif { .. } => Ok(()),
+            FrameWriter::Apng(writer) => Ok(writer.finish()?),
+            FrameWriter::PngSequence { folder, .. } => {
+                encode_mp4(&folder, path, options.frames_per_second)
+            }
+        }
+    }
+}
+
+fn encode_mp4(
+    frames_folder: &Path,
+    path: &Path,
+    frames_per_second: u32,
+) -> Result<(), EncodingError> {
     // Helpful info on ffmpeg commands here:
     // https://unix.stackexchange.com/questions/28803/how-can-i-reduce-a-videos-size-with-ffmpeg
     let reader = cmd!(
         "ffmpeg",
+        "-y", // Overwrite the previous timelapse.
         "-framerate",
-        "15", // 15 fps
-        "-pattern_type",
-        "glob",
+        frames_per_second.to_string(),
         "-i",
-        temp_images_path, // All images in the temp folder.
+        frames_folder.join("%05d.png"), // Numbered frames.
         "-vcodec",
-        "libx264", // Compress using the H.265 codec.
+        "libx264", // Compress using the H.264 codec.
         "-crf",
         "28", // Compression level. Higher is more compressed.
         "-pix_fmt",
-        "yuv420p",         // "Good for compatibility."
-        video_path_string  // Output file path.
+        "yuv420p", // "Good for compatibility."
+        path       // Output file path.
     )
     .stderr_to_stdout()
-    .reader()
-    .unwrap();
+    .reader()?;
 
-    let lines = BufReader::new(reader).lines();
-
-    for maybe_line in lines {
-        let line = maybe_line.unwrap();
-        println!("{line}");
+    for line in BufReader::new(reader).lines() {
+        println!("{}", line?);
     }
 
-    // Open the video we just created.
-    cmd!("open", video_path).run().unwrap();
-}
-
-fn get_created_time(dir_entry: &DirEntry) -> SystemTime {
-    dir_entry.metadata().unwrap().created().unwrap()
-}
-
-fn is_ds_store(dir_entry: &DirEntry) -> bool {
-    dir_entry.file_name().to_str().unwrap().contains("DS_Store")
-}
+    fs::remove_dir_all(frames_folder)?;
 
-// Pads a number with 5 zeros. Good for up to 99,999 values.
-fn pad_number(number: usize) -> String {
-    format!("{:05}", number)
+    Ok(())
 }
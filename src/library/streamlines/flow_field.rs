@@ -0,0 +1,97 @@
+use crate::prelude::*;
+
+// Anything streamlines can follow. Closures that return an angle work, and so
+// does FlowField.
+pub trait FlowDirection {
+    fn angle_at(&self, point: &Point2) -> NumberOfTurns;
+}
+
+impl<AngleAt> FlowDirection for AngleAt
+where
+    AngleAt: Fn(&Point2) -> NumberOfTurns,
+{
+    fn angle_at(&self, point: &Point2) -> NumberOfTurns {
+        self(point)
+    }
+}
+
+// A vector field in normalized coordinates. It can be sampled anywhere, drawn as a
+// grid of arrows to check what it looks like, and passed straight to
+// streamlines::new.
+pub struct FlowField {
+    vector_at: Box<dyn Fn(&Point2) -> Vec2>,
+}
+
+impl FlowField {
+    pub fn new<VectorAt>(vector_at: VectorAt) -> FlowField
+    where
+        VectorAt: Fn(&Point2) -> Vec2 + 'static,
+    {
+        FlowField {
+            vector_at: Box::new(vector_at),
+        }
+    }
+
+    // Swirls around without streamlines bunching up or spreading apart, because
+    // curl noise has no divergence.
+    pub fn curl_noise(rand: &Rand, frequency: f32) -> FlowField {
+        let rand = rand.clone();
+        FlowField::new(move |point| rand.curl_xy(&point.times(frequency)) * frequency)
+    }
+
+    // For fields that only have a direction. Every vector has a length of 1.
+    pub fn from_angles<AngleAt>(angle_at: AngleAt) -> FlowField
+    where
+        AngleAt: Fn(&Point2) -> NumberOfTurns + 'static,
+    {
+        FlowField::new(move |point| Vec2::from_angle(angle_at(point)))
+    }
+
+    pub fn vector_at(&self, point: &Point2) -> Vec2 {
+        (self.vector_at)(point)
+    }
+
+    // One arrow in the middle of each cell of a grid over the unit square, as
+    // [tail, tip]. They all have the same length, so only the direction shows.
+    pub fn arrows(&self, num_columns: usize, num_rows: usize) -> Vec<[Point2; 2]> {
+        let cell_wh = vec2(1.0 / num_columns as f32, 1.0 / num_rows as f32);
+        let half_length = cell_wh.x.min(cell_wh.y) * 0.4;
+
+        (0..num_rows)
+            .flat_map(|row| (0..num_columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let center = pt2(column as f32 + 0.5, row as f32 + 0.5) * cell_wh;
+                let direction = Vec2::from_angle(self.angle_at(&center)) * half_length;
+
+                [center - direction, center + direction]
+            })
+            .collect()
+    }
+
+    pub fn draw_arrows(
+        &self,
+        canvas: &Canvas,
+        num_columns: usize,
+        num_rows: usize,
+        stroke_weight: NormalizedF32,
+        color: impl Into<Hsla>,
+    ) {
+        let color = color.into();
+
+        for [tail, tip] in self.arrows(num_columns, num_rows) {
+            let head_size = tail.distance(tip) * 0.3;
+            let backwards = (tail - tip).resize(head_size);
+            let left = tip + Vec2::from_angle(backwards.normalized_angle() + 0.1) * head_size;
+            let right = tip + Vec2::from_angle(backwards.normalized_angle() - 0.1) * head_size;
+
+            canvas.polyline(vec![tail, tip], stroke_weight, color);
+            canvas.polyline(vec![left, tip, right], stroke_weight, color);
+        }
+    }
+}
+
+impl FlowDirection for FlowField {
+    fn angle_at(&self, point: &Point2) -> NumberOfTurns {
+        self.vector_at(point).normalized_angle()
+    }
+}
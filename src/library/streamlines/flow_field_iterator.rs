@@ -1,28 +1,28 @@
 use crate::prelude::*;
 use itertools::Itertools;
 
-pub struct FlowFieldIterator<'a, FlowField, ShouldContinue>
+pub struct FlowFieldIterator<'a, Field, ShouldContinue>
 where
-    FlowField: Fn(&Point2) -> NumberOfTurns,
+    Field: FlowDirection,
     ShouldContinue: Fn(&Point2) -> bool,
 {
     // other_streamlines: &'a [Streamline],
     pub current_point: Point2,
     pub step_size: NormalizedF32,
     pub move_forward: bool,
-    pub angle_at: &'a FlowField,
+    pub angle_at: &'a Field,
     pub should_continue: &'a ShouldContinue,
 }
 
-impl<'a, FlowField, ShouldContinue> FlowFieldIterator<'a, FlowField, ShouldContinue>
+impl<'a, Field, ShouldContinue> FlowFieldIterator<'a, Field, ShouldContinue>
 where
-    FlowField: Fn(&Point2) -> NumberOfTurns,
+    Field: FlowDirection,
     ShouldContinue: Fn(&Point2) -> bool,
 {
     pub fn iterate_forward_and_backward(
         start_point: Point2,
         step_size: f32,
-        angle_at: &FlowField,
+        angle_at: &Field,
         should_continue: &ShouldContinue,
     ) -> Vec<Point2> {
         // Goes from the middle (the seed point) to the end.
@@ -56,9 +56,9 @@ where
     }
 }
 
-impl<'a, FlowField, ShouldContinue> Iterator for FlowFieldIterator<'a, FlowField, ShouldContinue>
+impl<'a, Field, ShouldContinue> Iterator for FlowFieldIterator<'a, Field, ShouldContinue>
 where
-    FlowField: Fn(&Point2) -> NumberOfTurns,
+    Field: FlowDirection,
     ShouldContinue: Fn(&Point2) -> bool,
 {
     type Item = Point2;
@@ -76,9 +76,7 @@ where
             return None;
         }
 
-        let angle_at = self.angle_at;
-
-        let mut angle = angle_at(&self.current_point);
+        let mut angle = self.angle_at.angle_at(&self.current_point);
 
         if !self.move_forward {
             angle += 0.5;
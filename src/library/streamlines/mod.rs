@@ -1,6 +1,9 @@
 use crate::prelude::*;
 use std::ops::RangeInclusive;
 
+mod flow_field;
+pub use flow_field::*;
+
 mod flow_field_iterator;
 pub use flow_field_iterator::*;
 
@@ -13,16 +16,16 @@ pub use streamline_collection::*;
 mod point_cache;
 pub use point_cache::*;
 
-pub fn new<FlowField, GetMinDistanceForSeedPoints, GetMinDistanceForGrowthPoints, GetXAndYRange>(
+pub fn new<Field, GetMinDistanceForSeedPoints, GetMinDistanceForGrowthPoints, GetXAndYRange>(
     starting_seed_point: Point2,
     get_min_distance_for_seed_points: GetMinDistanceForSeedPoints,
     get_min_distance_for_growth_points: GetMinDistanceForGrowthPoints,
     step_size: NormalizedF32,
-    flow_field: &FlowField,
+    flow_field: &Field,
     get_x_and_y_ranges: &GetXAndYRange,
 ) -> Vec<Streamline>
 where
-    FlowField: Fn(&Point2) -> NumberOfTurns,
+    Field: FlowDirection,
     GetMinDistanceForSeedPoints: Fn(&Point2) -> NormalizedF32,
     GetMinDistanceForGrowthPoints: Fn(&Point2) -> NormalizedF32,
     GetXAndYRange: Fn(&Point2) -> [RangeInclusive<f32>; 2],
@@ -73,7 +76,7 @@ where
                         get_min_distance_for_growth_points(&seed_point),
                         seed_point,
                         step_size,
-                        &flow_field,
+                        flow_field,
                         x_range,
                         y_range,
                     );
@@ -12,18 +12,18 @@ pub struct Streamline {
 }
 
 impl Streamline {
-    pub fn from_flow_field<FlowField>(
+    pub fn from_flow_field<Field>(
         other_streamlines: &mut StreamlineCollection,
         min_distance_for_seed_points: NormalizedF32,
         min_distance_for_growth_points: NormalizedF32,
         seed_point: Point2,
         step_size: NormalizedF32,
-        angle_at: &FlowField,
+        angle_at: &Field,
         x_range: RangeInclusive<f32>,
         y_range: RangeInclusive<f32>,
     ) -> Option<Streamline>
     where
-        FlowField: Fn(&Point2) -> NumberOfTurns,
+        Field: FlowDirection,
     {
         let flow_field_iterator_should_continue =
             |point: &Point2| point.is_valid_growth_point(&x_range, &y_range, other_streamlines);
@@ -15,7 +15,45 @@ use prelude::*;
 
 fn main() {
     if should_create_timelapse() {
-        snapshot::timelapse::create();
+        if let Err(error) = snapshot::timelapse::create() {
+            eprintln!("{error}");
+        }
+        return;
+    }
+
+    if command_line::has_flag("--contact-sheet") {
+        let selection = snapshot::contact_sheet::Selection::from_command_line();
+        snapshot::contact_sheet::create(selection);
+        return;
+    }
+
+    if let Some(snapshot_name) = command_line::value_of("--materialize") {
+        snapshot::source_code::materialize(&snapshot_name);
+        return;
+    }
+
+    if let Some(snapshot_name) = command_line::value_of("--replay") {
+        snapshot::replay::replay(&snapshot_name);
+        return;
+    }
+
+    if let Some(snapshot_names) = command_line::values_of("--diff-snapshots", 2) {
+        snapshot::source_code::print_changes(&snapshot_names[0], &snapshot_names[1]);
+        return;
+    }
+
+    if command_line::has_flag("--deduplicate-examples") {
+        snapshot::source_store::deduplicate_examples();
+        return;
+    }
+
+    if let Some(seeds) = snapshot::seed::Seed::get_sweep_values_from_command_line() {
+        headless::render_seeds(seeds);
+        return;
+    }
+
+    if should_render_headlessly() {
+        headless::render();
         return;
     }
 
@@ -26,7 +64,7 @@ fn start(app: &App) -> Model {
     let [window_width, window_height] = get_window_dimensions();
     let monitor_is_bigger_than_window = check_if_monitor_is_bigger_than_window(app);
 
-    let _window_builder = app
+    let window_id = app
         .new_window()
         .view(draw)
         .size(window_width as u32, window_height as u32)
@@ -39,14 +77,16 @@ fn start(app: &App) -> Model {
         .build()
         .unwrap();
 
+    set_artwork_window(window_id);
+
     let loop_mode = get_loop_mode();
     app.set_loop_mode(loop_mode);
 
     Model::new(app)
 }
 
-fn update(app: &App, model: &mut Model, _update: Update) {
-    model.update(app);
+fn update(app: &App, model: &mut Model, update: Update) {
+    model.update(app, &update);
 }
 
 pub fn draw(app: &App, model: &Model, frame: Frame) {
@@ -55,7 +95,7 @@ pub fn draw(app: &App, model: &Model, frame: Frame) {
 
 fn capture_frame_on_s(app: &App, model: &mut Model, key: Key) {
     if key == Key::S {
-        model.snapshot.capture_frame(app);
+        model.capture_frame(app);
     }
 }
 
@@ -85,3 +125,7 @@ fn check_if_monitor_is_bigger_than_window(app: &App) -> bool {
 fn should_create_timelapse() -> bool {
     std::env::args().any(|argument| argument == "--create-timelapse")
 }
+
+fn should_render_headlessly() -> bool {
+    std::env::args().any(|argument| argument == "--headless")
+}
@@ -3,52 +3,281 @@ use crate::prelude::*;
 use std::cell::RefCell;
 
 pub struct Model {
-    pub snapshot: crate::snapshot::Snapshot,
+    pub snapshot: Snapshot,
     pub container: DenormalizedRect,
     pub done_rendering: bool,
     pub rand: Rand,
+    pub parameters: Parameters,
+    // Only open when the artwork declared some parameters and we have a window.
+    pub parameter_panel: Option<ParameterPanel>,
     loop_drawer: RefCell<LoopDrawer>,
+    // After the loop drawer, so a hot reloaded artwork gets dropped before the
+    // library it came from.
+    artwork_source: ArtworkSource,
+}
+
+// Where the artwork comes from. Usually artwork::create, compiled into the app.
+enum ArtworkSource {
+    Compiled,
+    HotReloaded(HotReloader),
+    Config(ArtworkConfig),
+}
+
+impl ArtworkSource {
+    fn from_command_line(app: Option<&App>) -> ArtworkSource {
+        if let Some(artwork_config) = ArtworkConfig::from_command_line() {
+            return ArtworkSource::Config(artwork_config);
+        }
+
+        // Only windowed runs stay open long enough to reload anything.
+        match app.and_then(|_| HotReloader::from_command_line()) {
+            Some(hot_reloader) => ArtworkSource::HotReloaded(hot_reloader),
+            None => ArtworkSource::Compiled,
+        }
+    }
+
+    fn create(&self, params: CreateArtworkParams) -> Box<dyn Artwork> {
+        match self {
+            ArtworkSource::Compiled => Box::new(artwork::create(params)),
+            ArtworkSource::HotReloaded(hot_reloader) => hot_reloader.create_artwork(params),
+            ArtworkSource::Config(artwork_config) => artwork_config
+                .create()
+                .unwrap_or_else(|error| panic!("{error}")),
+        }
+    }
 }
 
 impl Model {
     pub fn new(app: &App) -> Self {
-        let snapshot = snapshot::save();
+        Model::start(Some(app))
+    }
+
+    pub fn headless() -> Self {
+        Model::start(None)
+    }
+
+    pub fn headless_with_seed(seed: u64) -> Self {
+        Model::create(None, snapshot::save_with_seed(seed), None)
+    }
+
+    // Doesn't save a snapshot, so it's handy for tests.
+    pub fn unsaved(seed: u64) -> Self {
+        Model::create(None, Snapshot::unsaved(seed), None)
+    }
+
+    // Picks up from a checkpoint if we were asked to with --resume.
+    fn start(app: Option<&App>) -> Self {
+        match Checkpoint::from_command_line() {
+            Some(checkpoint) => {
+                let snapshot = Snapshot::resumed(&checkpoint.snapshot_name);
+                Model::create(app, snapshot, Some(checkpoint))
+            }
+            // Most hot reloaded versions get thrown away, so only save the ones we
+            // capture.
+            None if app.is_some() && command_line::has_flag("--hot-reload") => {
+                Model::create(app, snapshot::save_later(), None)
+            }
+            None => Model::create(app, snapshot::save(), None),
+        }
+    }
+
+    fn create(app: Option<&App>, mut snapshot: Snapshot, checkpoint: Option<Checkpoint>) -> Self {
         let rand = snapshot.get_rand();
         let container = get_container_rect();
 
-        let root_params = CreateArtworkParams {
-            app,
-            rand: &rand,
-            container: &container,
+        let mut parameters = Parameters::from_command_line();
+        if let Some(checkpoint) = &checkpoint {
+            parameters.set_all(checkpoint.parameters.clone());
+        }
+
+        let artwork_source = ArtworkSource::from_command_line(app);
+
+        let mut loop_drawer =
+            create_loop_drawer(app, &snapshot, &container, &parameters, &artwork_source);
+        set_post_processing(&mut snapshot, &loop_drawer);
+
+        // The artwork was just created with a fresh rand, same as the first time.
+        // Now we can carry on from where the rand was when we checkpointed.
+        let rand = match checkpoint {
+            Some(checkpoint) => {
+                let rand = Rand::from_state(&checkpoint.rand_state);
+                loop_drawer = loop_drawer.resuming(checkpoint);
+                rand
+            }
+            None => rand,
         };
 
-        let artwork = artwork::create(root_params);
-        let loop_drawer = LoopDrawer::new(artwork);
+        let parameter_panel = match app {
+            Some(app) if !parameters.is_empty() => Some(ParameterPanel::open(app)),
+            _ => None,
+        };
 
         Model {
             snapshot,
             container,
             done_rendering: false,
             rand,
+            parameters,
+            parameter_panel,
             loop_drawer: RefCell::new(loop_drawer),
+            artwork_source,
         }
     }
 
+    // Starts the render over with the same seed, e.g. after a parameter changed.
+    pub fn restart(&mut self, app: &App) {
+        self.parameters.clear_declarations();
+
+        let loop_drawer = create_loop_drawer(
+            Some(app),
+            &self.snapshot,
+            &self.container,
+            &self.parameters,
+            &self.artwork_source,
+        );
+        set_post_processing(&mut self.snapshot, &loop_drawer);
+        *self.loop_drawer.borrow_mut() = loop_drawer;
+
+        self.rand = self.snapshot.get_rand();
+        self.done_rendering = false;
+
+        app.set_loop_mode(LoopMode::RefreshSync);
+    }
+
     pub fn draw(&self, app: &App, frame: Frame) {
         self.loop_drawer
             .borrow_mut()
             .draw_artwork_in_a_loop(app, self, frame);
     }
 
-    pub fn update(&mut self, app: &App) {
-        if self.done_rendering {
+    pub fn draw_headlessly(&self, rasterizer: &mut Rasterizer) {
+        self.loop_drawer
+            .borrow_mut()
+            .draw_artwork_headlessly(self, rasterizer);
+    }
+
+    pub fn update(&mut self, app: &App, update: &Update) {
+        if let ArtworkSource::HotReloaded(hot_reloader) = &mut self.artwork_source {
+            if let Some(old_library) = hot_reloader.poll() {
+                self.start_new_snapshot_if_saved(app);
+                self.restart(app);
+                drop(old_library);
+                return;
+            }
+        }
+
+        if let Some(parameter_panel) = &mut self.parameter_panel {
+            if parameter_panel.update(update, &mut self.parameters) {
+                self.restart(app);
+                return;
+            }
+        }
+
+        if self.just_finished_rendering() {
+            self.capture_frame(app);
+
+            // The panel still needs updates to notice changes, so we wait for events
+            // instead of stopping.
+            if self.parameter_panel.is_some() {
+                app.set_loop_mode(LoopMode::Wait);
+            } else {
+                app.set_loop_mode(LoopMode::loop_once())
+            }
+        }
+    }
+
+    // The saved snapshot has the old source code, so anything we capture from the
+    // new code needs a snapshot of its own.
+    fn start_new_snapshot_if_saved(&mut self, app: &App) {
+        if self.snapshot.is_deferred() {
             return;
         }
 
-        if let DoneDrawing::Yes = self.loop_drawer.borrow_mut().update() {
-            self.snapshot.capture_frame(app);
+        snapshot::finish(app, &self.snapshot);
+
+        let seed = snapshot::seed::Seed::forced(self.snapshot.seed);
+        self.snapshot = Snapshot::deferred(seed);
+    }
+
+    pub fn update_headlessly(&mut self, rasterizer: &Rasterizer) {
+        if self.just_finished_rendering() {
+            let image_name = self
+                .snapshot
+                .save_rasterized_frame(&rasterizer.to_rgba_image(), self.parameters.values());
+            self.loop_drawer.borrow().save_svg(&image_name);
+        }
+    }
+
+    pub fn capture_frame(&mut self, app: &App) {
+        if let ArtworkSource::HotReloaded(hot_reloader) = &self.artwork_source {
+            if !hot_reloader.matches_source() {
+                println!("src/ doesn't match the loaded artwork, so the snapshot wouldn't either. Capture again once it's rebuilt, or restart if it was more than the artwork that changed.");
+                return;
+            }
+        }
+
+        let image_name = self.snapshot.capture_frame(app, self.parameters.values());
+        self.loop_drawer.borrow().save_svg(&image_name);
+    }
+
+    fn just_finished_rendering(&mut self) -> bool {
+        if self.done_rendering {
+            return false;
+        }
+
+        let mut loop_drawer = self.loop_drawer.borrow_mut();
+
+        if let DoneDrawing::Yes = loop_drawer.update() {
             self.done_rendering = true;
-            app.set_loop_mode(LoopMode::loop_once())
+
+            if let Some(render_duration) = loop_drawer.render_duration() {
+                self.snapshot
+                    .metadata
+                    .record_render(loop_drawer.num_repeats(), render_duration);
+            }
         }
+
+        self.done_rendering
+    }
+}
+
+// --post-process replaces the artwork's own filters.
+fn set_post_processing(snapshot: &mut Snapshot, loop_drawer: &LoopDrawer) {
+    let post_processing =
+        PostProcessing::from_command_line().unwrap_or_else(|| loop_drawer.post_processing());
+
+    snapshot.post_processing = post_processing;
+}
+
+fn create_loop_drawer(
+    app: Option<&App>,
+    snapshot: &Snapshot,
+    container: &DenormalizedRect,
+    parameters: &Parameters,
+    artwork_source: &ArtworkSource,
+) -> LoopDrawer {
+    let rand = snapshot.get_rand();
+
+    let root_params = CreateArtworkParams {
+        app,
+        rand: &rand,
+        container,
+        parameters,
+    };
+
+    let artwork = artwork_source.create(root_params);
+    let frame_recorder = FrameRecorder::from_command_line(&snapshot.source_code_folder_name);
+    let scheduling = DrawScheduling::from_command_line(app.is_none());
+
+    let loop_drawer = LoopDrawer::from_box(artwork)
+        .scheduling(scheduling)
+        .recording_frames(frame_recorder);
+
+    // Checkpoints point at the snapshot, so they're no use until it's saved.
+    if snapshot.is_deferred() {
+        loop_drawer
+    } else {
+        let checkpointer = Checkpointer::from_command_line(&snapshot.source_code_folder_name);
+        loop_drawer.checkpointing(checkpointer)
     }
 }
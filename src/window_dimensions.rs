@@ -1,4 +1,6 @@
 use crate::prelude::*;
+use once_cell::sync::OnceCell;
+use std::cell::Ref;
 
 const unscaled_window_width: f32 = 1000.0;
 const unscaled_window_height: f32 = 1000.0;
@@ -6,6 +8,28 @@ pub const window_scale: f32 = 1.0;
 
 pub const container_scale: f32 = 0.9;
 
+// How wide the window is when printed or plotted. Used for vector output, e.g. SVGs.
+pub const print_width_millimeters: f32 = 300.0;
+
+static artwork_window_id: OnceCell<WindowId> = OnceCell::new();
+
+pub fn set_artwork_window(window_id: WindowId) {
+    artwork_window_id.set(window_id).ok();
+}
+
+pub fn get_artwork_window_id() -> Option<WindowId> {
+    artwork_window_id.get().copied()
+}
+
+// The window the artwork draws into. App::main_window is whichever window has
+// focus, which isn't the artwork when the parameter panel is open.
+pub fn artwork_window(app: &App) -> Ref<window::Window> {
+    artwork_window_id
+        .get()
+        .and_then(|window_id| app.window(*window_id))
+        .unwrap_or_else(|| app.main_window())
+}
+
 pub fn get_window_dimensions() -> [f32; 2] {
     let window_width = unscaled_window_width.times(window_scale).round();
     let window_height = unscaled_window_height.times(window_scale).round();
@@ -38,3 +62,8 @@ pub fn aspect_ratio() -> f32 {
     let [window_width, window_height] = get_window_dimensions();
     window_width / window_height
 }
+
+pub fn millimeters_per_pixel() -> f32 {
+    let [window_width, _window_height] = get_window_dimensions();
+    print_width_millimeters / window_width
+}